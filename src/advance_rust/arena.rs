@@ -0,0 +1,652 @@
+#[cfg(feature = "count-allocations")]
+use std::alloc::{GlobalAlloc, Layout, System};
+#[cfg(feature = "count-allocations")]
+use std::cell::Cell;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::hint::black_box;
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+use std::rc::Rc;
+
+use crate::advance_rust::smart_pointers::List;
+use crate::collections::bench::{measure, report};
+use crate::test_support::catch_quiet_panic;
+
+pub fn arena() {
+    // Arenas, an alternative to Rc for lists and graphs
+
+    // Rc lets a node have many owners, but every node is its own heap allocation, every clone and
+    // drop touches a reference count, and a graph with cycles leaks unless some edges are Weak.
+    // An arena keeps all nodes in one Vec and nodes point at each other by index instead:
+    //  allocation is pushing to the Vec, so thousands of nodes cost a handful of allocations
+    //  nodes sit next to each other in memory, which makes traversals cache friendly
+    //  cycles are just indices, dropping the arena frees everything at once
+    // The catch is that an index can outlive its node. Every slot has a generation that goes up
+    // when its node is removed, and a NodeId remembers the generation it was made with, so an id
+    // of a removed node (a stale id) is caught instead of silently pointing at whatever reused
+    // the slot. NodeId<T> is also typed, an id from an Arena<String> can't be used on an Arena<i32>.
+
+    let mut lists = ArenaList::new();
+    let a = lists.push_front(None, 10);
+    let a = lists.push_front(Some(a), 5);
+    let b = lists.push_front(Some(a), 3);
+    let c = lists.push_front(Some(a), 4);
+    println!(
+        "arena lists b = {:?}, c = {:?}, sharing {} nodes",
+        lists.iter(Some(b)).collect::<Vec<_>>(),
+        lists.iter(Some(c)).collect::<Vec<_>>(),
+        lists.node_count()
+    );
+
+    let mut graph = Graph::new();
+    let rust = graph.add_node("rust");
+    let cargo = graph.add_node("cargo");
+    graph.add_edge(rust, cargo);
+    graph.add_edge(cargo, rust);
+    println!(
+        "reachable from rust: {:?}",
+        graph
+            .reachable_from(rust)
+            .into_iter()
+            .map(|id| graph[id])
+            .collect::<Vec<_>>()
+    );
+
+    it_catches_stale_ids();
+    it_shares_tails_like_the_rc_list();
+    it_builds_graphs_with_cycles();
+    #[cfg(feature = "count-allocations")]
+    it_allocates_less_than_the_rc_list();
+}
+
+// A typed index into an Arena<T>. Copy, and Send/Sync whatever T is, it's only two numbers.
+pub struct NodeId<T> {
+    index: u32,
+    generation: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+// Derives would require T: Clone, T: PartialEq, ... which an id doesn't need
+impl<T> Clone for NodeId<T> {
+    fn clone(&self) -> NodeId<T> {
+        *self
+    }
+}
+
+impl<T> Copy for NodeId<T> {}
+
+impl<T> PartialEq for NodeId<T> {
+    fn eq(&self, other: &NodeId<T>) -> bool {
+        self.index == other.index && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for NodeId<T> {}
+
+impl<T> Hash for NodeId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for NodeId<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NodeId({}v{})", self.index, self.generation)
+    }
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    // indices of empty slots, reused before the Vec grows
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Arena<T> {
+        Arena {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, value: T) -> NodeId<T> {
+        self.len += 1;
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize].value = Some(value);
+                index
+            }
+            None => {
+                let index = u32::try_from(self.slots.len()).expect("arena is full");
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                index
+            }
+        };
+        NodeId {
+            index,
+            generation: self.slots[index as usize].generation,
+            marker: PhantomData,
+        }
+    }
+
+    // None if the id is stale, the node was already removed
+    pub fn remove(&mut self, id: NodeId<T>) -> Option<T> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        let value = slot.value.take()?;
+        self.len -= 1;
+        // a slot whose generation would wrap around is never reused, or a very old id could
+        // become valid again
+        if let Some(generation) = slot.generation.checked_add(1) {
+            slot.generation = generation;
+            self.free.push(id.index);
+        }
+        Some(value)
+    }
+
+    pub fn get(&self, id: NodeId<T>) -> Option<&T> {
+        match self.slots.get(id.index as usize) {
+            Some(slot) if slot.generation == id.generation => slot.value.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: NodeId<T>) -> Option<&mut T> {
+        match self.slots.get_mut(id.index as usize) {
+            Some(slot) if slot.generation == id.generation => slot.value.as_mut(),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, id: NodeId<T>) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Every live node with its id, in slot order
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId<T>, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let value = slot.value.as_ref()?;
+            let id = NodeId {
+                index: index as u32,
+                generation: slot.generation,
+                marker: PhantomData,
+            };
+            Some((id, value))
+        })
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Arena<T> {
+        Arena::new()
+    }
+}
+
+// arena[id] panics on a stale id, like vec[i] does on an index out of bounds
+impl<T> Index<NodeId<T>> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: NodeId<T>) -> &T {
+        match self.get(id) {
+            Some(value) => value,
+            None => panic!("stale {:?}, its node was removed", id),
+        }
+    }
+}
+
+impl<T> IndexMut<NodeId<T>> for Arena<T> {
+    fn index_mut(&mut self, id: NodeId<T>) -> &mut T {
+        match self.get_mut(id) {
+            Some(value) => value,
+            None => panic!("stale {:?}, its node was removed", id),
+        }
+    }
+}
+
+pub struct ListNode<T> {
+    value: T,
+    next: Option<ListId<T>>,
+}
+
+pub type ListId<T> = NodeId<ListNode<T>>;
+
+// The persistent List from smart_pointers with its nodes in an arena. A list is the id of its first
+// node, None is the empty list, and many lists can share a tail just like with Rc. Nodes are freed
+// all together when the ArenaList is dropped.
+pub struct ArenaList<T> {
+    nodes: Arena<ListNode<T>>,
+}
+
+impl<T> ArenaList<T> {
+    pub fn new() -> ArenaList<T> {
+        ArenaList::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> ArenaList<T> {
+        ArenaList {
+            nodes: Arena::with_capacity(capacity),
+        }
+    }
+
+    // tail stays untouched and becomes the tail of the returned list
+    pub fn push_front(&mut self, tail: Option<ListId<T>>, value: T) -> ListId<T> {
+        self.nodes.insert(ListNode { value, next: tail })
+    }
+
+    pub fn head(&self, list: ListId<T>) -> Option<&T> {
+        self.nodes.get(list).map(|node| &node.value)
+    }
+
+    pub fn tail(&self, list: ListId<T>) -> Option<ListId<T>> {
+        self.nodes.get(list).and_then(|node| node.next)
+    }
+
+    pub fn iter(&self, list: Option<ListId<T>>) -> ArenaListIter<'_, T> {
+        ArenaListIter {
+            nodes: &self.nodes,
+            next: list,
+        }
+    }
+
+    pub fn len(&self, list: Option<ListId<T>>) -> usize {
+        self.iter(list).count()
+    }
+
+    // nodes of all lists together, shared tails counted once
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+impl<T> Default for ArenaList<T> {
+    fn default() -> ArenaList<T> {
+        ArenaList::new()
+    }
+}
+
+pub struct ArenaListIter<'a, T> {
+    nodes: &'a Arena<ListNode<T>>,
+    next: Option<ListId<T>>,
+}
+
+impl<'a, T> Iterator for ArenaListIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        let node = self.nodes.get(self.next?)?;
+        self.next = node.next;
+        Some(&node.value)
+    }
+}
+
+pub struct GraphNode<T> {
+    value: T,
+    edges: Vec<GraphId<T>>,
+}
+
+pub type GraphId<T> = NodeId<GraphNode<T>>;
+
+// A directed graph where any node can point at any other, cycles included, with no Rc or Weak.
+// Removing a node doesn't hunt down the edges pointing at it: they become stale ids, which the
+// generation check skips, and they are dropped the next time their node's edges are changed.
+pub struct Graph<T> {
+    nodes: Arena<GraphNode<T>>,
+}
+
+impl<T> Graph<T> {
+    pub fn new() -> Graph<T> {
+        Graph {
+            nodes: Arena::new(),
+        }
+    }
+
+    pub fn add_node(&mut self, value: T) -> GraphId<T> {
+        self.nodes.insert(GraphNode {
+            value,
+            edges: Vec::new(),
+        })
+    }
+
+    // false if either node is gone
+    pub fn add_edge(&mut self, from: GraphId<T>, to: GraphId<T>) -> bool {
+        if !self.nodes.contains(to) {
+            return false;
+        }
+        let Some(node) = self.nodes.get_mut(from) else {
+            return false;
+        };
+        node.edges.push(to);
+        let edges = std::mem::take(&mut node.edges);
+        let edges = edges
+            .into_iter()
+            .filter(|edge| self.nodes.contains(*edge))
+            .collect();
+        self.nodes[from].edges = edges;
+        true
+    }
+
+    pub fn remove_node(&mut self, id: GraphId<T>) -> Option<T> {
+        self.nodes.remove(id).map(|node| node.value)
+    }
+
+    pub fn get(&self, id: GraphId<T>) -> Option<&T> {
+        self.nodes.get(id).map(|node| &node.value)
+    }
+
+    pub fn get_mut(&mut self, id: GraphId<T>) -> Option<&mut T> {
+        self.nodes.get_mut(id).map(|node| &mut node.value)
+    }
+
+    pub fn contains(&self, id: GraphId<T>) -> bool {
+        self.nodes.contains(id)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // The nodes id has an edge to, nothing if id is stale
+    pub fn neighbors(&self, id: GraphId<T>) -> impl Iterator<Item = GraphId<T>> + '_ {
+        self.nodes
+            .get(id)
+            .into_iter()
+            .flat_map(|node| node.edges.iter().copied())
+            .filter(|edge| self.nodes.contains(*edge))
+    }
+
+    // Depth first, every node once even with cycles, starting with id itself
+    pub fn reachable_from(&self, id: GraphId<T>) -> Vec<GraphId<T>> {
+        let mut reachable = Vec::new();
+        if !self.contains(id) {
+            return reachable;
+        }
+        // indexed by slot: neighbors only yields live ids, and a slot holds one live id at a time
+        let mut seen = vec![false; self.nodes.slots.len()];
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if seen[id.index as usize] {
+                continue;
+            }
+            seen[id.index as usize] = true;
+            reachable.push(id);
+            let mut next: Vec<_> = self.neighbors(id).collect();
+            // reversed so the first edge is visited first
+            next.reverse();
+            stack.extend(next);
+        }
+        reachable
+    }
+}
+
+impl<T> Default for Graph<T> {
+    fn default() -> Graph<T> {
+        Graph::new()
+    }
+}
+
+impl<T> Index<GraphId<T>> for Graph<T> {
+    type Output = T;
+
+    fn index(&self, id: GraphId<T>) -> &T {
+        &self.nodes[id].value
+    }
+}
+
+impl<T> IndexMut<GraphId<T>> for Graph<T> {
+    fn index_mut(&mut self, id: GraphId<T>) -> &mut T {
+        &mut self.nodes[id].value
+    }
+}
+
+fn build_rc_list(len: u64) -> Rc<List<u64>> {
+    let mut list = List::new();
+    for value in 0..len {
+        list = list.push_front(value);
+    }
+    list
+}
+
+fn build_arena_list(len: u64) -> (ArenaList<u64>, Option<ListId<u64>>) {
+    let mut lists = ArenaList::new();
+    let mut list = None;
+    for value in 0..len {
+        list = Some(lists.push_front(list, value));
+    }
+    (lists, list)
+}
+
+// Counting allocations
+// Only with `--features count-allocations`, which makes this the global allocator of the whole
+// binary. Without the feature every program, demo and the bench-map timings use the plain system
+// allocator, and bench-arena only reports timings.
+//  cargo run --release --features count-allocations -- bench-arena
+#[cfg(feature = "count-allocations")]
+struct CountingAllocator;
+
+#[cfg(feature = "count-allocations")]
+thread_local! {
+    // const and without Drop, so using it never allocates itself
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+#[cfg(feature = "count-allocations")]
+fn count_allocation() {
+    // fails while the thread is being torn down, those allocations just aren't counted
+    let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+}
+
+// The system allocator, counting the allocations (and reallocations) made on each thread
+#[cfg(feature = "count-allocations")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[cfg(feature = "count-allocations")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// How many allocations f made on this thread, and what it returned
+#[cfg(feature = "count-allocations")]
+pub fn count_allocations<R, F: FnOnce() -> R>(f: F) -> (u64, R) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}
+
+// Builds and walks a list of len elements both ways
+pub fn bench_lists(len: u64) {
+    let rc_list = build_rc_list(len);
+    let (lists, arena_list) = build_arena_list(len);
+    println!("ArenaList vs Rc<List>, {} elements, best of 5 rounds", len);
+
+    #[cfg(feature = "count-allocations")]
+    {
+        let (rc_allocations, _) = count_allocations(|| build_rc_list(len));
+        let (arena_allocations, _) = count_allocations(|| build_arena_list(len));
+        println!(
+            "{:<28} {:>10} {:>10}   {:>10} {:>10}",
+            "allocations", "Rc<List>", rc_allocations, "ArenaList", arena_allocations
+        );
+    }
+    #[cfg(not(feature = "count-allocations"))]
+    println!("(build with --features count-allocations to count allocations)");
+
+    let baseline = measure(|| drop(black_box(build_rc_list(len))));
+    let ours = measure(|| drop(black_box(build_arena_list(len))));
+    report("build + drop", "Rc<List>", baseline, "ArenaList", ours);
+
+    let baseline = measure(|| {
+        black_box(rc_list.iter().sum::<u64>());
+    });
+    let ours = measure(|| {
+        black_box(lists.iter(arena_list).sum::<u64>());
+    });
+    report("traverse", "Rc<List>", baseline, "ArenaList", ours);
+}
+
+pub fn run_cli(args: &[String]) -> Result<(), String> {
+    let len = match args.first() {
+        Some(len) => len
+            .parse()
+            .map_err(|_| format!("list length must be a number, got {}", len))?,
+        None => 1_000_000,
+    };
+    bench_lists(len);
+    Ok(())
+}
+
+fn it_catches_stale_ids() {
+    let mut arena = Arena::new();
+    let a = arena.insert("a");
+    let b = arena.insert("b");
+    assert_eq!(arena.remove(a), Some("a"));
+    assert_eq!(arena.remove(a), None);
+
+    // c reuses a's slot, but with the next generation
+    let c = arena.insert("c");
+    assert_eq!(arena.len(), 2);
+    assert_eq!(arena.get(a), None);
+    assert_eq!(arena[c], "c");
+    assert_ne!(a, c);
+    assert_eq!(format!("{:?} {:?}", a, c), "NodeId(0v0) NodeId(0v1)");
+
+    arena[b] = "B";
+    assert_eq!(
+        arena.iter().map(|(_, value)| *value).collect::<Vec<_>>(),
+        ["c", "B"]
+    );
+
+    let message = catch_quiet_panic(|| {
+        let _ = arena[a];
+    });
+    assert_eq!(
+        message.as_deref(),
+        Some("stale NodeId(0v0), its node was removed")
+    );
+
+    arena.remove(b);
+    arena.remove(c);
+    assert!(arena.is_empty());
+}
+
+fn it_shares_tails_like_the_rc_list() {
+    let mut lists = ArenaList::new();
+    let a = lists.push_front(None, 10);
+    let a = lists.push_front(Some(a), 5);
+    let b = lists.push_front(Some(a), 3);
+    let c = lists.push_front(Some(a), 4);
+
+    assert_eq!(lists.iter(Some(b)).copied().collect::<Vec<_>>(), [3, 5, 10]);
+    assert_eq!(lists.iter(Some(c)).copied().collect::<Vec<_>>(), [4, 5, 10]);
+    assert_eq!(lists.head(c), Some(&4));
+    assert_eq!(lists.tail(b), Some(a));
+    assert_eq!(lists.tail(c), lists.tail(b));
+    assert_eq!(lists.len(None), 0);
+    assert_eq!(lists.len(Some(b)), 3);
+    // 5 and 10 are stored once for both lists, like the Rc version
+    assert_eq!(lists.node_count(), 4);
+}
+
+fn it_builds_graphs_with_cycles() {
+    let mut graph = Graph::new();
+    let a = graph.add_node('a');
+    let b = graph.add_node('b');
+    let c = graph.add_node('c');
+    assert!(graph.add_edge(a, b));
+    assert!(graph.add_edge(b, c));
+    assert!(graph.add_edge(c, a));
+    assert!(graph.add_edge(a, c));
+    assert_eq!(graph.reachable_from(b), [b, c, a]);
+    assert_eq!(graph.neighbors(a).collect::<Vec<_>>(), [b, c]);
+
+    // the edge a -> b goes stale with b, and stays stale when d moves into b's slot
+    assert_eq!(graph.remove_node(b), Some('b'));
+    let d = graph.add_node('d');
+    assert!(!graph.contains(b));
+    assert!(!graph.add_edge(d, b));
+    assert_eq!(graph.neighbors(a).collect::<Vec<_>>(), [c]);
+    assert_eq!(graph.reachable_from(a), [a, c]);
+    assert!(graph.reachable_from(b).is_empty());
+
+    *graph.get_mut(d).unwrap() = 'D';
+    graph[c] = 'C';
+    assert_eq!(graph.get(d), Some(&'D'));
+    assert_eq!(graph[c], 'C');
+    assert_eq!(graph.node_count(), 3);
+
+    // a long chain with an edge back to the start, one visit per node
+    let mut graph = Graph::new();
+    let ids: Vec<_> = (0..10_000).map(|value| graph.add_node(value)).collect();
+    for pair in ids.windows(2) {
+        graph.add_edge(pair[0], pair[1]);
+    }
+    graph.add_edge(ids[9_999], ids[0]);
+    assert_eq!(graph.reachable_from(ids[5_000]).len(), 10_000);
+}
+
+#[cfg(feature = "count-allocations")]
+fn it_allocates_less_than_the_rc_list() {
+    // one heap allocation per Rc node, plus one for the Nil at the end
+    let (allocations, list) = count_allocations(|| build_rc_list(1000));
+    assert_eq!(allocations, 1001);
+    assert_eq!(list.len(), 1000);
+
+    // the arena only allocates when its Vec grows, so about log2(1000) times
+    let (allocations, (lists, list)) = count_allocations(|| build_arena_list(1000));
+    assert!(allocations <= 12, "{} allocations", allocations);
+    assert_eq!(lists.iter(list).sum::<u64>(), 999 * 1000 / 2);
+
+    // and only once with the capacity known up front
+    let (allocations, _) = count_allocations(|| {
+        let mut lists = ArenaList::with_capacity(1000);
+        let mut list = None;
+        for value in 0..1000 {
+            list = Some(lists.push_front(list, value));
+        }
+        lists
+    });
+    assert_eq!(allocations, 1);
+}
@@ -0,0 +1,403 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::{self, Instant};
+
+use crate::advance_rust::smart_pointers::{
+    default_thresholds, Messenger, MockMessenger, Threshold, ThresholdAlerts,
+};
+
+pub async fn async_messengers() {
+    // Sending messages from async code
+
+    // Messenger::send is a plain function. Called from a tokio task, a slow one (an SMTP server
+    // taking seconds to answer) blocks the runtime's worker thread, and every other task on it waits.
+    // AsyncMessenger::send returns a future instead, so while a message is on its way the runtime
+    // runs other tasks.
+
+    // On top of that:
+    //  Retry wraps any AsyncMessenger, gives every attempt a timeout and retries failures with an
+    //  exponential backoff (wait, then twice as long, ...) up to a maximum
+    //  AsyncLimitTracker is LimitTracker that awaits the messenger and reports failed delivery
+    //  SyncAdapter makes any Messenger an AsyncMessenger (fine for quick ones like MockMessenger),
+    //  BlockingAdapter runs a slow Messenger on tokio's blocking thread pool with spawn_blocking
+
+    let messenger = Retry::new(SyncAdapter(MockMessenger::new()));
+    let mut tracker = AsyncLimitTracker::new(&messenger, 100);
+    tracker.set_value(95).await.unwrap();
+    println!(
+        "async tracker sent {:?}",
+        messenger.inner().0.sent_messages.borrow()
+    );
+
+    it_adapts_sync_messengers().await;
+    it_retries_with_backoff().await;
+    it_times_out_hanging_attempts().await;
+    it_reports_failed_delivery_from_the_tracker().await;
+    it_does_not_block_the_runtime();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendError {
+    // the attempt did not finish within the timeout
+    Timeout(Duration),
+    Failed(String),
+}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Timeout(after) => write!(f, "sending timed out after {:?}", after),
+            SendError::Failed(reason) => write!(f, "sending failed: {}", reason),
+        }
+    }
+}
+
+impl Error for SendError {}
+
+// The future isn't required to be Send, so messengers with RefCells in them (MockMessenger)
+// can be used too. They can't be moved to another thread with tokio::spawn then, only awaited.
+pub trait AsyncMessenger {
+    fn send(&self, msg: &str) -> impl Future<Output = Result<(), SendError>>;
+}
+
+// Calls a sync Messenger right on the runtime thread, only for messengers that return quickly
+pub struct SyncAdapter<M: Messenger>(pub M);
+
+impl<M: Messenger> AsyncMessenger for SyncAdapter<M> {
+    async fn send(&self, msg: &str) -> Result<(), SendError> {
+        self.0.send(msg);
+        Ok(())
+    }
+}
+
+// Runs a sync Messenger on the blocking thread pool, the runtime keeps going while it sends
+pub struct BlockingAdapter<M: Messenger + Send + Sync + 'static>(pub Arc<M>);
+
+impl<M: Messenger + Send + Sync + 'static> AsyncMessenger for BlockingAdapter<M> {
+    async fn send(&self, msg: &str) -> Result<(), SendError> {
+        let messenger = Arc::clone(&self.0);
+        let msg = String::from(msg);
+        tokio::task::spawn_blocking(move || messenger.send(&msg))
+            .await
+            .map_err(|err| SendError::Failed(err.to_string()))
+    }
+}
+
+pub struct Retry<M: AsyncMessenger> {
+    inner: M,
+    attempts: usize,
+    timeout: Duration,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl<M: AsyncMessenger> Retry<M> {
+    // 3 attempts of 5 seconds each, waiting 100ms and then 200ms in between
+    pub fn new(inner: M) -> Retry<M> {
+        Retry {
+            inner,
+            attempts: 3,
+            timeout: Duration::from_secs(5),
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+
+    pub fn attempts(mut self, attempts: usize) -> Retry<M> {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Retry<M> {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Retry<M> {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    // Doubles up to max_backoff, saturating so huge backoffs don't overflow Duration
+    fn next_backoff(&self, backoff: Duration) -> Duration {
+        backoff.saturating_mul(2).min(self.max_backoff)
+    }
+}
+
+impl<M: AsyncMessenger> AsyncMessenger for Retry<M> {
+    // Returns the error of the last attempt if none of them got through
+    async fn send(&self, msg: &str) -> Result<(), SendError> {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            let result = match time::timeout(self.timeout, self.inner.send(msg)).await {
+                Ok(result) => result,
+                Err(_) => Err(SendError::Timeout(self.timeout)),
+            };
+            if result.is_ok() || attempt == self.attempts {
+                return result;
+            }
+            time::sleep(backoff).await;
+            backoff = self.next_backoff(backoff);
+            attempt += 1;
+        }
+    }
+}
+
+pub struct AsyncLimitTracker<'a, M: AsyncMessenger> {
+    messenger: &'a M,
+    value: usize,
+    max: usize,
+    alerts: ThresholdAlerts,
+}
+
+impl<'a, M: AsyncMessenger> AsyncLimitTracker<'a, M> {
+    pub fn new(messenger: &'a M, max: usize) -> AsyncLimitTracker<'a, M> {
+        AsyncLimitTracker::with_thresholds(messenger, max, default_thresholds())
+    }
+
+    pub fn with_thresholds(
+        messenger: &'a M,
+        max: usize,
+        thresholds: Vec<Threshold>,
+    ) -> AsyncLimitTracker<'a, M> {
+        AsyncLimitTracker {
+            messenger,
+            value: 0,
+            max,
+            alerts: ThresholdAlerts::new(thresholds),
+        }
+    }
+
+    pub fn value(&self) -> usize {
+        self.value
+    }
+
+    // Like LimitTracker::set_value, but waits for the message to be delivered. If it couldn't be,
+    // the threshold still counts as notified, so the next call doesn't send it again.
+    pub async fn set_value(&mut self, value: usize) -> Result<(), SendError> {
+        self.value = value;
+
+        let percentage_of_max = self.value as f64 / self.max as f64 * 100.0;
+
+        match self.alerts.update(percentage_of_max) {
+            Some(threshold) => self.messenger.send(&threshold.message).await,
+            None => Ok(()),
+        }
+    }
+}
+
+enum Step {
+    Deliver,
+    Fail,
+    // never finishes, only a timeout gets us out
+    Hang,
+}
+
+// An AsyncMessenger that works through a script of what happens to each attempt
+struct ScriptedMessenger {
+    script: RefCell<VecDeque<Step>>,
+    delivered: RefCell<Vec<String>>,
+    attempts: RefCell<Vec<Instant>>,
+}
+
+impl ScriptedMessenger {
+    fn new(script: Vec<Step>) -> ScriptedMessenger {
+        ScriptedMessenger {
+            script: RefCell::new(script.into()),
+            delivered: RefCell::new(vec![]),
+            attempts: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl AsyncMessenger for ScriptedMessenger {
+    async fn send(&self, msg: &str) -> Result<(), SendError> {
+        self.attempts.borrow_mut().push(Instant::now());
+        // take the step before awaiting, no RefCell borrow may be held across an await
+        let step = self
+            .script
+            .borrow_mut()
+            .pop_front()
+            .unwrap_or(Step::Deliver);
+        match step {
+            Step::Deliver => {
+                self.delivered.borrow_mut().push(String::from(msg));
+                Ok(())
+            }
+            Step::Fail => Err(SendError::Failed(String::from("server said no"))),
+            Step::Hang => {
+                std::future::pending::<()>().await;
+                unreachable!()
+            }
+        }
+    }
+}
+
+async fn it_adapts_sync_messengers() {
+    // a Messenger that can be shared with the blocking pool
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl Messenger for Recorder {
+        fn send(&self, msg: &str) {
+            self.0.lock().unwrap().push(String::from(msg));
+        }
+    }
+
+    let sync = SyncAdapter(MockMessenger::new());
+    sync.send("inline").await.unwrap();
+    assert_eq!(*sync.0.sent_messages.borrow(), ["inline"]);
+
+    let recorder = Arc::new(Recorder(Mutex::new(vec![])));
+    let blocking = BlockingAdapter(Arc::clone(&recorder));
+    blocking.send("on the blocking pool").await.unwrap();
+    assert_eq!(*recorder.0.lock().unwrap(), ["on the blocking pool"]);
+}
+
+async fn it_retries_with_backoff() {
+    let messenger = Retry::new(ScriptedMessenger::new(vec![
+        Step::Fail,
+        Step::Fail,
+        Step::Deliver,
+    ]))
+    .attempts(4)
+    .backoff(Duration::from_millis(10), Duration::from_millis(15));
+
+    messenger.send("third time lucky").await.unwrap();
+    assert_eq!(*messenger.inner().delivered.borrow(), ["third time lucky"]);
+
+    // waited 10ms, then 20ms capped to 15ms
+    let attempts = messenger.inner().attempts.borrow().clone();
+    assert_eq!(attempts.len(), 3);
+    assert!(attempts[1] - attempts[0] >= Duration::from_millis(10));
+    assert!(attempts[2] - attempts[1] >= Duration::from_millis(15));
+
+    // giving up returns the last error
+    let messenger = Retry::new(ScriptedMessenger::new(vec![Step::Fail, Step::Fail]))
+        .attempts(2)
+        .backoff(Duration::from_millis(1), Duration::from_millis(1));
+    assert_eq!(
+        messenger.send("lost").await,
+        Err(SendError::Failed(String::from("server said no")))
+    );
+    assert!(messenger.inner().delivered.borrow().is_empty());
+
+    // huge backoffs stay at the cap instead of overflowing
+    let messenger =
+        Retry::new(ScriptedMessenger::new(vec![])).backoff(Duration::MAX, Duration::MAX);
+    assert_eq!(messenger.next_backoff(Duration::MAX), Duration::MAX);
+}
+
+async fn it_times_out_hanging_attempts() {
+    let messenger = Retry::new(ScriptedMessenger::new(vec![Step::Hang, Step::Deliver]))
+        .timeout(Duration::from_millis(20))
+        .backoff(Duration::from_millis(1), Duration::from_millis(1));
+
+    let start = Instant::now();
+    messenger.send("after a timeout").await.unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert_eq!(messenger.inner().attempts.borrow().len(), 2);
+
+    let messenger = Retry::new(ScriptedMessenger::new(vec![Step::Hang]))
+        .attempts(1)
+        .timeout(Duration::from_millis(5));
+    let err = messenger.send("lost").await.unwrap_err();
+    assert_eq!(err, SendError::Timeout(Duration::from_millis(5)));
+    assert_eq!(err.to_string(), "sending timed out after 5ms");
+}
+
+async fn it_reports_failed_delivery_from_the_tracker() {
+    let messenger = ScriptedMessenger::new(vec![Step::Fail]);
+    let mut tracker = AsyncLimitTracker::new(&messenger, 100);
+
+    assert!(tracker.set_value(80).await.is_err());
+    // the 75% warning was attempted, it isn't sent again for the same crossing
+    tracker.set_value(85).await.unwrap();
+    assert!(messenger.delivered.borrow().is_empty());
+
+    tracker.set_value(100).await.unwrap();
+    assert_eq!(tracker.value(), 100);
+    assert_eq!(
+        *messenger.delivered.borrow(),
+        ["Error: You are over your quota!"]
+    );
+}
+
+fn it_does_not_block_the_runtime() {
+    // main runs on a multi-thread runtime, where another worker could run the ticker while send
+    // blocks. With a single thread, a send that blocks it stops every other task. It gets a thread
+    // of its own, a runtime can't be started from inside main's.
+    std::thread::spawn(|| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let ticks = Arc::new(AtomicUsize::new(0));
+            let ticker = tokio::spawn({
+                let ticks = Arc::clone(&ticks);
+                async move {
+                    loop {
+                        ticks.fetch_add(1, Ordering::SeqCst);
+                        tokio::task::yield_now().await;
+                    }
+                }
+            });
+            tokio::task::yield_now().await;
+
+            // the blocking pool sends while the ticker keeps running on the runtime thread
+            let blocking = BlockingAdapter(Arc::new(WaitForTicks::new(&ticks)));
+            let before = ticks.load(Ordering::SeqCst);
+            blocking.send("slow").await.unwrap();
+            assert!(ticks.load(Ordering::SeqCst) >= before + 3);
+
+            // SyncAdapter sends on the runtime thread, the ticker can't run until it is done
+            let sync = SyncAdapter(WaitForTicks::new(&ticks));
+            let before = ticks.load(Ordering::SeqCst);
+            sync.send("slow").await.unwrap();
+            assert_eq!(ticks.load(Ordering::SeqCst), before);
+
+            ticker.abort();
+        });
+    })
+    .join()
+    .unwrap();
+}
+
+// A slow Messenger, like an SMTP server: finishes once the ticker has ticked 3 more times, or
+// gives up after 200ms when the ticker can't run
+struct WaitForTicks {
+    ticks: Arc<AtomicUsize>,
+}
+
+impl WaitForTicks {
+    fn new(ticks: &Arc<AtomicUsize>) -> WaitForTicks {
+        WaitForTicks {
+            ticks: Arc::clone(ticks),
+        }
+    }
+}
+
+impl Messenger for WaitForTicks {
+    fn send(&self, _msg: &str) {
+        let target = self.ticks.load(Ordering::SeqCst) + 3;
+        let start = std::time::Instant::now();
+        while self.ticks.load(Ordering::SeqCst) < target
+            && start.elapsed() < Duration::from_millis(200)
+        {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+
+pub fn drop_tracer() {
+    // Tracing the Order of Drops
+
+    // CustomPointer prints when it is dropped, which shows that drop runs, but to check the order we
+    // have to read the output. TracedPointer does the same thing but writes every drop into a global
+    // log, with a sequence number and the thread it happened on, so the order can be asserted.
+
+    // The rules the tests below pin down:
+    // local variables are dropped in reverse order of declaration, at the end of their scope
+    // a shadowed variable is not dropped when it is shadowed, only at the end of the scope
+    // a struct runs its own Drop first, then drops its fields in declaration order
+    // a Vec drops its elements from first to last
+    // a moved value is dropped by whoever owns it last, not where it was created
+    // std::mem::drop just takes ownership, so the value is dropped right there
+
+    let trace = DropTrace::start();
+    {
+        let _a = TracedPointer::new("a");
+        let _b = TracedPointer::new("b");
+        let c = TracedPointer::new("c");
+        drop(c);
+    }
+    for event in trace.events() {
+        println!(
+            "#{} dropped {} on thread {}",
+            event.sequence,
+            event.name,
+            event.thread_name.as_deref().unwrap_or("<unnamed>")
+        );
+    }
+    drop(trace);
+
+    it_drops_locals_in_reverse_order();
+    it_drops_shadowed_variables_at_the_end_of_the_scope();
+    it_drops_struct_fields_in_declaration_order();
+    it_drops_vector_elements_from_first_to_last();
+    it_drops_moved_values_with_their_new_owner();
+    it_records_the_dropping_thread();
+    it_only_keeps_events_a_live_trace_can_see();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropEvent {
+    pub name: String,
+    pub sequence: u64,
+    pub thread_id: ThreadId,
+    pub thread_name: Option<String>,
+}
+
+// Only the events a live DropTrace can still see are kept: nothing is logged while no trace is
+// running, and when a trace is dropped everything older than the oldest remaining trace goes.
+// A program that drops TracedPointers all the time doesn't grow the log without bound.
+struct DropLog {
+    events: Vec<DropEvent>,
+    // the first_sequence of every live DropTrace
+    traces: Vec<u64>,
+}
+
+static NEXT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+static DROP_LOG: Mutex<DropLog> = Mutex::new(DropLog {
+    events: Vec::new(),
+    traces: Vec::new(),
+});
+
+// Appends a drop event to the global log, for types that want to trace drops in their own Drop impl
+pub fn record_drop(name: &str) {
+    let mut log = DROP_LOG.lock().unwrap();
+    // taking the sequence number under the lock keeps the log sorted by sequence
+    let sequence = NEXT_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    if log.traces.is_empty() {
+        return;
+    }
+    let current = thread::current();
+    log.events.push(DropEvent {
+        name: String::from(name),
+        sequence,
+        thread_id: current.id(),
+        thread_name: current.name().map(String::from),
+    });
+}
+
+// CustomPointer, with the println replaced by an entry in the drop log
+#[derive(Debug)]
+pub struct TracedPointer {
+    pub data: String,
+}
+
+impl TracedPointer {
+    pub fn new(data: &str) -> TracedPointer {
+        TracedPointer {
+            data: String::from(data),
+        }
+    }
+}
+
+impl Drop for TracedPointer {
+    fn drop(&mut self) {
+        record_drop(&self.data);
+    }
+}
+
+// The drops logged since start(), so one test doesn't see another's events
+pub struct DropTrace {
+    first_sequence: u64,
+}
+
+impl DropTrace {
+    pub fn start() -> DropTrace {
+        // under the lock, so no drop can be half way through recording
+        let mut log = DROP_LOG.lock().unwrap();
+        let first_sequence = NEXT_SEQUENCE.load(Ordering::Relaxed);
+        log.traces.push(first_sequence);
+        DropTrace { first_sequence }
+    }
+
+    pub fn events(&self) -> Vec<DropEvent> {
+        let log = DROP_LOG.lock().unwrap();
+        let start = log
+            .events
+            .partition_point(|event| event.sequence < self.first_sequence);
+        log.events[start..].to_vec()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.events().into_iter().map(|event| event.name).collect()
+    }
+}
+
+impl Drop for DropTrace {
+    fn drop(&mut self) {
+        // a panicking test can poison the lock, the log is still consistent then
+        let mut log = DROP_LOG.lock().unwrap_or_else(|err| err.into_inner());
+        let index = log
+            .traces
+            .iter()
+            .position(|first| *first == self.first_sequence)
+            .unwrap();
+        log.traces.swap_remove(index);
+        let keep_from = log.traces.iter().min().copied().unwrap_or(u64::MAX);
+        let start = log
+            .events
+            .partition_point(|event| event.sequence < keep_from);
+        log.events.drain(..start);
+    }
+}
+
+// How many events the log holds right now
+fn logged_events() -> usize {
+    DROP_LOG.lock().unwrap().events.len()
+}
+
+fn it_drops_locals_in_reverse_order() {
+    let trace = DropTrace::start();
+    {
+        let _first = TracedPointer::new("first");
+        let _second = TracedPointer::new("second");
+        let _third = TracedPointer::new("third");
+    }
+    assert_eq!(trace.names(), ["third", "second", "first"]);
+
+    // `let _ = ...` doesn't bind anything, so the value is dropped immediately
+    let trace = DropTrace::start();
+    {
+        let _kept = TracedPointer::new("kept");
+        let _ = TracedPointer::new("ignored");
+        record_drop("end of scope");
+    }
+    assert_eq!(trace.names(), ["ignored", "end of scope", "kept"]);
+
+    let events = trace.events();
+    assert!(events
+        .windows(2)
+        .all(|pair| pair[0].sequence < pair[1].sequence));
+}
+
+fn it_drops_shadowed_variables_at_the_end_of_the_scope() {
+    let trace = DropTrace::start();
+    {
+        let x = TracedPointer::new("x1");
+        let x = TracedPointer::new(&format!("{} shadowed by x2", x.data));
+        record_drop("end of scope");
+        assert_eq!(x.data, "x1 shadowed by x2");
+    }
+    assert_eq!(trace.names(), ["end of scope", "x1 shadowed by x2", "x1"]);
+
+    // assigning to a variable drops the old value right away, unlike shadowing
+    let trace = DropTrace::start();
+    {
+        let mut x = TracedPointer::new("old");
+        assert_eq!(x.data, "old");
+        x = TracedPointer::new("new");
+        record_drop("end of scope");
+        assert_eq!(x.data, "new");
+    }
+    assert_eq!(trace.names(), ["old", "end of scope", "new"]);
+}
+
+struct Outer {
+    first: TracedPointer,
+    second: TracedPointer,
+}
+
+impl Drop for Outer {
+    fn drop(&mut self) {
+        record_drop("outer");
+    }
+}
+
+fn it_drops_struct_fields_in_declaration_order() {
+    let trace = DropTrace::start();
+    let outer = Outer {
+        // built in this order, but dropped in declaration order anyway
+        second: TracedPointer::new("second"),
+        first: TracedPointer::new("first"),
+    };
+    assert_eq!(
+        (outer.first.data.as_str(), outer.second.data.as_str()),
+        ("first", "second")
+    );
+    drop(outer);
+    assert_eq!(trace.names(), ["outer", "first", "second"]);
+
+    // tuples work the same way
+    let trace = DropTrace::start();
+    drop((TracedPointer::new("0"), TracedPointer::new("1")));
+    assert_eq!(trace.names(), ["0", "1"]);
+}
+
+fn it_drops_vector_elements_from_first_to_last() {
+    let trace = DropTrace::start();
+    let mut vec: Vec<TracedPointer> = ["a", "b", "c", "d"]
+        .iter()
+        .map(|name| TracedPointer::new(name))
+        .collect();
+
+    let removed = vec.remove(1);
+    vec.truncate(2);
+    assert_eq!(trace.names(), ["d"]);
+
+    drop(vec);
+    assert_eq!(trace.names(), ["d", "a", "c"]);
+    drop(removed);
+    assert_eq!(trace.names(), ["d", "a", "c", "b"]);
+}
+
+fn take_ownership(pointer: TracedPointer) {
+    record_drop(&format!("inside with {}", pointer.data));
+}
+
+fn it_drops_moved_values_with_their_new_owner() {
+    let trace = DropTrace::start();
+    {
+        let moved = TracedPointer::new("moved into a function");
+        take_ownership(moved);
+
+        let original = TracedPointer::new("moved out of its scope");
+        let outlives;
+        {
+            let inner = original;
+            outlives = inner;
+            record_drop("inner scope ends");
+        }
+        record_drop("outer scope ends");
+        assert_eq!(outlives.data, "moved out of its scope");
+
+        let early = TracedPointer::new("dropped early");
+        std::mem::drop(early);
+        record_drop("after mem::drop");
+    }
+    assert_eq!(
+        trace.names(),
+        [
+            "inside with moved into a function",
+            "moved into a function",
+            "inner scope ends",
+            "outer scope ends",
+            "dropped early",
+            "after mem::drop",
+            "moved out of its scope",
+        ]
+    );
+}
+
+fn it_records_the_dropping_thread() {
+    let trace = DropTrace::start();
+    let pointer = TracedPointer::new("sent to a worker");
+    let worker = thread::Builder::new()
+        .name(String::from("drop-worker"))
+        .spawn(move || drop(pointer))
+        .unwrap();
+    let worker_id = worker.thread().id();
+    worker.join().unwrap();
+    drop(TracedPointer::new("stayed home"));
+
+    let events = trace.events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].name, "sent to a worker");
+    assert_eq!(events[0].thread_id, worker_id);
+    assert_eq!(events[0].thread_name.as_deref(), Some("drop-worker"));
+    assert_eq!(events[1].thread_id, thread::current().id());
+    assert_eq!(events[1].thread_name.as_deref(), Some("main"));
+}
+
+fn it_only_keeps_events_a_live_trace_can_see() {
+    // every trace before this one is gone, and with them their events
+    assert_eq!(logged_events(), 0);
+    drop(TracedPointer::new("nobody is tracing"));
+    assert_eq!(logged_events(), 0);
+
+    let outer = DropTrace::start();
+    drop(TracedPointer::new("outer"));
+    let inner = DropTrace::start();
+    drop(TracedPointer::new("inner"));
+    assert_eq!(logged_events(), 2);
+
+    // outer still needs both events
+    drop(inner);
+    assert_eq!(outer.names(), ["outer", "inner"]);
+    assert_eq!(logged_events(), 2);
+
+    // a trace started later than a live one only trims up to the older one
+    let later = DropTrace::start();
+    drop(TracedPointer::new("later"));
+    drop(outer);
+    assert_eq!(later.names(), ["later"]);
+    assert_eq!(logged_events(), 1);
+    drop(later);
+    assert_eq!(logged_events(), 0);
+}
@@ -0,0 +1,320 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+pub fn leak_detector() {
+    // Reference Cycles Can Leak Memory
+
+    // Rust makes memory leaks hard but not impossible. With Rc<T> and RefCell<T> we can create
+    // references where items refer to each other in a cycle. The strong_count of each item in the
+    // cycle never reaches 0, so the values are never dropped and their memory is never freed.
+
+    // The compiler will not catch this, and nothing panics either, the memory is just gone.
+    // TrackedRc<T> is an Rc<T> that writes every allocation into a global registry and removes it
+    // again when the value is dropped. Anything still in the registry after all our handles are gone
+    // was leaked.
+
+    // The book's cycle example: a -> b and then a's tail is pointed back at b, so b -> a -> b -> ...
+    let checkpoint = LeakCheckpoint::new();
+    let a_weak = {
+        let a = TrackedRc::new("a", Cons(5, RefCell::new(Tail::Owned(nil()))));
+        let b = TrackedRc::new("b", Cons(10, RefCell::new(Tail::Owned(a.clone()))));
+        println!(
+            "a rc count after b creation = {}",
+            TrackedRc::strong_count(&a)
+        );
+
+        if let Some(link) = a.tail() {
+            *link.borrow_mut() = Tail::Owned(b.clone());
+        }
+        println!(
+            "b rc count after changing a = {}",
+            TrackedRc::strong_count(&b)
+        );
+        println!(
+            "a rc count after changing a = {}",
+            TrackedRc::strong_count(&a)
+        );
+
+        // walking the list now never reaches Nil: 5 10 5 10 ...
+        let mut current = a.clone();
+        let mut values = Vec::new();
+        for _ in 0..4 {
+            values.push(current.head().unwrap());
+            let next = current.tail().unwrap().borrow().next().unwrap();
+            current = next;
+        }
+        assert_eq!(values, [5, 10, 5, 10]);
+        assert!(TrackedRc::ptr_eq(&current, &a));
+
+        TrackedRc::downgrade(&a)
+    };
+
+    // a and b are out of scope, but each still holds the other alive
+    let report = checkpoint.report();
+    println!("{}", report);
+    assert_eq!(report.labels(), ["a", "b"]);
+
+    // we kept a Weak around, so we can still reach the cycle and cut it by hand
+    if let Some(a) = a_weak.upgrade() {
+        *a.tail().unwrap().borrow_mut() = Tail::Owned(nil());
+    }
+    assert!(checkpoint.report().is_clean());
+
+    // The fix: the link that closes the loop must not own what it points at, so it is a Weak.
+    let checkpoint = LeakCheckpoint::new();
+    {
+        let a = TrackedRc::new("a", Cons(5, RefCell::new(Tail::Owned(nil()))));
+        let b = TrackedRc::new("b", Cons(10, RefCell::new(Tail::Owned(a.clone()))));
+        if let Some(link) = a.tail() {
+            *link.borrow_mut() = Tail::Back(TrackedRc::downgrade(&b));
+        }
+        let next = a.tail().unwrap().borrow().next().unwrap();
+        assert!(TrackedRc::ptr_eq(&next, &b));
+        drop(next);
+        println!(
+            "with a Weak back link: a strong = {}, b strong = {}, b weak = {}",
+            TrackedRc::strong_count(&a),
+            TrackedRc::strong_count(&b),
+            TrackedRc::weak_count(&b)
+        );
+    }
+    let report = checkpoint.report();
+    println!("{}", report);
+    assert!(report.is_clean());
+
+    it_only_reports_allocations_made_after_the_checkpoint();
+}
+
+// Registry of every TrackedRc allocation that is still alive, keyed by a global id.
+// Ids only go up, so "created after the checkpoint" is simply "id >= the checkpoint's first id".
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static REGISTRY: Mutex<BTreeMap<u64, LiveAllocation>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveAllocation {
+    pub id: u64,
+    pub label: String,
+    pub type_name: &'static str,
+}
+
+// The value together with its registry id. Dropping it (when the last TrackedRc goes away)
+// removes the entry from the registry.
+struct Tracked<T> {
+    id: u64,
+    value: T,
+}
+
+impl<T> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        REGISTRY.lock().unwrap().remove(&self.id);
+    }
+}
+
+pub struct TrackedRc<T> {
+    inner: Rc<Tracked<T>>,
+}
+
+impl<T> TrackedRc<T> {
+    pub fn new(label: &str, value: T) -> TrackedRc<T> {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        REGISTRY.lock().unwrap().insert(
+            id,
+            LiveAllocation {
+                id,
+                label: String::from(label),
+                type_name: std::any::type_name::<T>(),
+            },
+        );
+        TrackedRc {
+            inner: Rc::new(Tracked { id, value }),
+        }
+    }
+
+    // Associated functions instead of methods, like Rc::strong_count, so they never shadow
+    // a method of T reached through Deref
+    pub fn strong_count(this: &TrackedRc<T>) -> usize {
+        Rc::strong_count(&this.inner)
+    }
+
+    pub fn weak_count(this: &TrackedRc<T>) -> usize {
+        Rc::weak_count(&this.inner)
+    }
+
+    pub fn downgrade(this: &TrackedRc<T>) -> TrackedWeak<T> {
+        TrackedWeak {
+            inner: Rc::downgrade(&this.inner),
+        }
+    }
+
+    pub fn ptr_eq(this: &TrackedRc<T>, other: &TrackedRc<T>) -> bool {
+        Rc::ptr_eq(&this.inner, &other.inner)
+    }
+}
+
+impl<T> Clone for TrackedRc<T> {
+    fn clone(&self) -> TrackedRc<T> {
+        TrackedRc {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Deref for TrackedRc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner.value
+    }
+}
+
+pub struct TrackedWeak<T> {
+    inner: Weak<Tracked<T>>,
+}
+
+impl<T> TrackedWeak<T> {
+    pub fn upgrade(&self) -> Option<TrackedRc<T>> {
+        self.inner.upgrade().map(|inner| TrackedRc { inner })
+    }
+}
+
+impl<T> Clone for TrackedWeak<T> {
+    fn clone(&self) -> TrackedWeak<T> {
+        TrackedWeak {
+            inner: Weak::clone(&self.inner),
+        }
+    }
+}
+
+// Remembers where the registry stood, report() lists what was created since then and is still alive
+pub struct LeakCheckpoint {
+    first_id: u64,
+}
+
+impl LeakCheckpoint {
+    pub fn new() -> LeakCheckpoint {
+        LeakCheckpoint {
+            first_id: NEXT_ID.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn report(&self) -> LeakReport {
+        let registry = REGISTRY.lock().unwrap();
+        LeakReport {
+            live: registry
+                .range(self.first_id..)
+                .map(|(_, allocation)| allocation.clone())
+                .collect(),
+        }
+    }
+}
+
+pub struct LeakReport {
+    pub live: Vec<LiveAllocation>,
+}
+
+impl LeakReport {
+    pub fn is_clean(&self) -> bool {
+        self.live.is_empty()
+    }
+
+    pub fn labels(&self) -> Vec<&str> {
+        self.live
+            .iter()
+            .map(|allocation| allocation.label.as_str())
+            .collect()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "no allocations alive since the checkpoint");
+        }
+        write!(
+            f,
+            "{} allocation(s) still alive since the checkpoint:",
+            self.live.len()
+        )?;
+        for allocation in &self.live {
+            write!(
+                f,
+                "\n  #{} {} ({})",
+                allocation.id, allocation.label, allocation.type_name
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// The book's List with a mutable tail, which is what makes a cycle possible.
+// Owned tails are strong, a Back tail is a Weak link that closes a loop without owning it.
+pub enum List {
+    Cons(i32, RefCell<Tail>),
+    Nil,
+}
+
+pub enum Tail {
+    Owned(TrackedRc<List>),
+    Back(TrackedWeak<List>),
+}
+
+use List::{Cons, Nil};
+
+impl List {
+    pub fn head(&self) -> Option<i32> {
+        match self {
+            Cons(value, _) => Some(*value),
+            Nil => None,
+        }
+    }
+
+    pub fn tail(&self) -> Option<&RefCell<Tail>> {
+        match self {
+            Cons(_, item) => Some(item),
+            Nil => None,
+        }
+    }
+}
+
+impl Tail {
+    // The list this tail points at, None once a Back link's target has been dropped
+    pub fn next(&self) -> Option<TrackedRc<List>> {
+        match self {
+            Tail::Owned(next) => Some(next.clone()),
+            Tail::Back(next) => next.upgrade(),
+        }
+    }
+}
+
+fn nil() -> TrackedRc<List> {
+    TrackedRc::new("nil", Nil)
+}
+
+fn it_only_reports_allocations_made_after_the_checkpoint() {
+    let before = TrackedRc::new("before", 1);
+    let checkpoint = LeakCheckpoint::new();
+    let after = TrackedRc::new("after", String::from("still here"));
+    let dropped = TrackedRc::new("dropped", 3);
+    drop(dropped);
+
+    let report = checkpoint.report();
+    assert_eq!(report.labels(), ["after"]);
+    assert_eq!(report.live[0].type_name, "alloc::string::String");
+    assert!(report.to_string().contains("after (alloc::string::String)"));
+
+    // the registry entry lives as long as the value, not as long as one particular handle
+    let weak = TrackedRc::downgrade(&after);
+    let clone = after.clone();
+    drop(after);
+    assert_eq!(checkpoint.report().labels(), ["after"]);
+    drop(clone);
+    assert!(weak.upgrade().is_none());
+    assert!(checkpoint.report().is_clean());
+    assert_eq!(*before, 1);
+}
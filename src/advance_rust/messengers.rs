@@ -0,0 +1,598 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::advance_rust::scope_guards::TempDir;
+use crate::advance_rust::smart_pointers::{LimitTracker, Messenger};
+
+pub fn messengers() {
+    // Messengers that deliver
+
+    // MockMessenger only remembers what it was asked to send. These send it somewhere:
+    //  WriteMessenger: one line per message to stdout, stderr or any other io::Write
+    //  FileMessenger: appends timestamped lines to a log file
+    //  ChannelMessenger: hands messages to another part of the program through an mpsc channel
+    //  SmtpMessenger: emails the message through an SMTP server
+    // Messenger::send can't return an error (LimitTracker has nothing to do with one), so each of
+    // them also has a try_send that does, and send reports a failed delivery on stderr.
+
+    let stdout = WriteMessenger::stdout();
+    let mut tracker = LimitTracker::new(&stdout, 100);
+    tracker.set_value(95);
+    WriteMessenger::stderr().send("(and this line went to stderr)");
+
+    let (channel, alerts) = ChannelMessenger::new();
+    let mut tracker = LimitTracker::new(&channel, 100);
+    tracker.set_value(80);
+    tracker.set_value(100);
+    for alert in alerts.try_iter() {
+        println!("alert from the channel: {}", alert);
+    }
+
+    let dir = TempDir::new("messengers").unwrap();
+    let file = FileMessenger::new(dir.path().join("alerts.log"));
+    LimitTracker::new(&file, 10).set_value(10);
+    print!("{}", std::fs::read_to_string(file.path()).unwrap());
+
+    let server = FakeSmtpServer::start(1);
+    let smtp =
+        SmtpMessenger::new(&server.address, "quota@example.com", &["ops@example.com"]).unwrap();
+    LimitTracker::new(&smtp, 100).set_value(76);
+    let mails = server.finish();
+    println!("mail to {:?}:\n{}", mails[0].recipients, mails[0].data);
+
+    it_writes_one_line_per_message();
+    it_appends_to_a_log_file();
+    it_forwards_messages_through_a_channel();
+    it_sends_mail_through_smtp();
+    it_reports_smtp_rejections();
+    it_rejects_line_breaks_in_addresses_and_subject();
+}
+
+// Writes every message as a line to out
+pub struct WriteMessenger<W: Write> {
+    out: RefCell<W>,
+}
+
+impl WriteMessenger<io::Stdout> {
+    pub fn stdout() -> WriteMessenger<io::Stdout> {
+        WriteMessenger::new(io::stdout())
+    }
+}
+
+impl WriteMessenger<io::Stderr> {
+    pub fn stderr() -> WriteMessenger<io::Stderr> {
+        WriteMessenger::new(io::stderr())
+    }
+}
+
+impl<W: Write> WriteMessenger<W> {
+    pub fn new(out: W) -> WriteMessenger<W> {
+        WriteMessenger {
+            out: RefCell::new(out),
+        }
+    }
+
+    pub fn try_send(&self, msg: &str) -> io::Result<()> {
+        let mut out = self.out.borrow_mut();
+        writeln!(out, "{}", msg)?;
+        out.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out.into_inner()
+    }
+}
+
+impl<W: Write> Messenger for WriteMessenger<W> {
+    fn send(&self, msg: &str) {
+        if let Err(err) = self.try_send(msg) {
+            eprintln!("could not write message {:?}: {}", msg, err);
+        }
+    }
+}
+
+// Appends "<unix seconds> <message>" lines to a file. The file is opened for every message, so it
+// can be rotated or deleted while the program runs and other processes can append to it too.
+pub struct FileMessenger {
+    path: PathBuf,
+}
+
+impl FileMessenger {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileMessenger {
+        FileMessenger { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn try_send(&self, msg: &str) -> io::Result<()> {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        // one message per line, so a newline inside a message is written as \n
+        let line = format!("{} {}\n", seconds, msg.replace('\n', "\\n"));
+        // a single write in append mode, so lines from different writers don't interleave
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+}
+
+impl Messenger for FileMessenger {
+    fn send(&self, msg: &str) {
+        if let Err(err) = self.try_send(msg) {
+            eprintln!(
+                "could not append message {:?} to {}: {}",
+                msg,
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+// Sends every message down an mpsc channel, whoever holds the Receiver gets them
+pub struct ChannelMessenger {
+    sender: Sender<String>,
+}
+
+impl ChannelMessenger {
+    pub fn new() -> (ChannelMessenger, Receiver<String>) {
+        let (sender, receiver) = mpsc::channel();
+        (ChannelMessenger { sender }, receiver)
+    }
+
+    // Fails once the Receiver has been dropped
+    pub fn try_send(&self, msg: &str) -> Result<(), mpsc::SendError<String>> {
+        self.sender.send(String::from(msg))
+    }
+}
+
+impl Messenger for ChannelMessenger {
+    fn send(&self, msg: &str) {
+        if self.try_send(msg).is_err() {
+            eprintln!("could not send message {:?}: nobody is listening", msg);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SmtpError {
+    Io(io::Error),
+    // the server answered a command with something other than what we expected
+    Rejected { command: String, reply: String },
+    // an address or the subject contains a CR or LF, nothing was sent
+    LineBreak { field: &'static str, value: String },
+}
+
+impl fmt::Display for SmtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SmtpError::Io(err) => write!(f, "smtp connection failed: {}", err),
+            SmtpError::Rejected { command, reply } => {
+                write!(f, "smtp server rejected {}: {}", command, reply)
+            }
+            SmtpError::LineBreak { field, value } => {
+                write!(f, "smtp {} contains a line break: {:?}", field, value)
+            }
+        }
+    }
+}
+
+impl Error for SmtpError {}
+
+impl From<io::Error> for SmtpError {
+    fn from(err: io::Error) -> SmtpError {
+        SmtpError::Io(err)
+    }
+}
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Emails every message through an SMTP server, one connection per message.
+// Plain SMTP only: no TLS and no authentication, so meant for a relay on localhost or the local network.
+// The addresses go into MAIL FROM/RCPT TO commands and the subject into a header, each ended by CRLF,
+// so a line break in any of them would let the text after it inject its own commands or headers.
+// They are rejected up front.
+pub struct SmtpMessenger {
+    server: String,
+    from: String,
+    to: Vec<String>,
+    subject: String,
+}
+
+impl SmtpMessenger {
+    pub fn new(server: &str, from: &str, to: &[&str]) -> Result<SmtpMessenger, SmtpError> {
+        check_no_line_break("sender", from)?;
+        for to in to {
+            check_no_line_break("recipient", to)?;
+        }
+        Ok(SmtpMessenger {
+            server: String::from(server),
+            from: String::from(from),
+            to: to.iter().map(|to| String::from(*to)).collect(),
+            subject: String::from("Quota alert"),
+        })
+    }
+
+    pub fn subject(mut self, subject: &str) -> Result<SmtpMessenger, SmtpError> {
+        check_no_line_break("subject", subject)?;
+        self.subject = String::from(subject);
+        Ok(self)
+    }
+
+    pub fn try_send(&self, msg: &str) -> Result<(), SmtpError> {
+        let address = self
+            .server
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address for server"))?;
+        let stream = TcpStream::connect_timeout(&address, SMTP_TIMEOUT)?;
+        stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+        stream.set_write_timeout(Some(SMTP_TIMEOUT))?;
+        let mut session = SmtpSession {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+
+        session.expect("connect", 220)?;
+        session.command("HELO localhost", 250)?;
+        session.command(&format!("MAIL FROM:<{}>", self.from), 250)?;
+        for to in &self.to {
+            session.command(&format!("RCPT TO:<{}>", to), 250)?;
+        }
+        session.command("DATA", 354)?;
+        session.write_data(&self.message(msg))?;
+        session.expect("message", 250)?;
+        session.command("QUIT", 221)?;
+        Ok(())
+    }
+
+    fn message(&self, body: &str) -> String {
+        format!(
+            "From: <{}>\r\nTo: {}\r\nSubject: {}\r\n\r\n{}",
+            self.from,
+            self.to
+                .iter()
+                .map(|to| format!("<{}>", to))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.subject,
+            body
+        )
+    }
+}
+
+fn check_no_line_break(field: &'static str, value: &str) -> Result<(), SmtpError> {
+    if value.contains(['\r', '\n']) {
+        return Err(SmtpError::LineBreak {
+            field,
+            value: String::from(value),
+        });
+    }
+    Ok(())
+}
+
+impl Messenger for SmtpMessenger {
+    fn send(&self, msg: &str) {
+        if let Err(err) = self.try_send(msg) {
+            eprintln!("could not email message {:?}: {}", msg, err);
+        }
+    }
+}
+
+struct SmtpSession {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl SmtpSession {
+    fn command(&mut self, command: &str, code: u16) -> Result<(), SmtpError> {
+        write!(self.writer, "{}\r\n", command)?;
+        self.expect(command, code)
+    }
+
+    // Reads a whole reply (continuation lines look like "250-...") and checks its code
+    fn expect(&mut self, command: &str, code: u16) -> Result<(), SmtpError> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(SmtpError::Rejected {
+                    command: String::from(command),
+                    reply: String::from("connection closed"),
+                });
+            }
+            let line = line.trim_end();
+            if line.len() > 3 && line.as_bytes()[3] == b'-' {
+                continue;
+            }
+            if line.get(..3).and_then(|reply| reply.parse().ok()) == Some(code) {
+                return Ok(());
+            }
+            return Err(SmtpError::Rejected {
+                command: String::from(command),
+                reply: String::from(line),
+            });
+        }
+    }
+
+    // Sends the message with CRLF line endings and dot-stuffing: a line starting with "." gets an
+    // extra "." so it can't be mistaken for the "." that ends the message
+    fn write_data(&mut self, message: &str) -> io::Result<()> {
+        let mut data = String::new();
+        for line in message.lines() {
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push_str(".\r\n");
+        self.writer.write_all(data.as_bytes())
+    }
+}
+
+// A mail as the fake server received it
+#[derive(Debug, Clone, PartialEq)]
+struct ReceivedMail {
+    from: String,
+    recipients: Vec<String>,
+    // headers and body, with the dot-stuffing undone and \n line endings
+    data: String,
+}
+
+// Just enough of an SMTP server on 127.0.0.1 to test SmtpMessenger against.
+// It handles `connections` connections one after the other and then stops.
+struct FakeSmtpServer {
+    address: String,
+    handle: JoinHandle<Vec<ReceivedMail>>,
+}
+
+impl FakeSmtpServer {
+    fn start(connections: usize) -> FakeSmtpServer {
+        FakeSmtpServer::start_rejecting(connections, None)
+    }
+
+    // Answers RCPT TO for this address with 550
+    fn start_rejecting(connections: usize, reject: Option<&str>) -> FakeSmtpServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let reject = reject.map(String::from);
+        let handle = thread::spawn(move || {
+            let mut mails = Vec::new();
+            for _ in 0..connections {
+                let (stream, _) = listener.accept().unwrap();
+                if let Some(mail) = serve(stream, reject.as_deref()).unwrap() {
+                    mails.push(mail);
+                }
+            }
+            mails
+        });
+        FakeSmtpServer { address, handle }
+    }
+
+    fn finish(self) -> Vec<ReceivedMail> {
+        self.handle.join().unwrap()
+    }
+}
+
+fn serve(stream: TcpStream, reject: Option<&str>) -> io::Result<Option<ReceivedMail>> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut mail = ReceivedMail {
+        from: String::new(),
+        recipients: Vec::new(),
+        data: String::new(),
+    };
+    let mut delivered = false;
+
+    writer.write_all(b"220 fake.smtp ready\r\n")?;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? != 0 {
+        let command = line.trim_end().to_string();
+        line.clear();
+        if command.starts_with("HELO") || command.starts_with("EHLO") {
+            writer.write_all(b"250-fake.smtp\r\n250 OK\r\n")?;
+        } else if let Some(from) = command.strip_prefix("MAIL FROM:") {
+            mail.from = from.trim_matches(|c| c == '<' || c == '>').to_string();
+            writer.write_all(b"250 OK\r\n")?;
+        } else if let Some(to) = command.strip_prefix("RCPT TO:") {
+            let to = to.trim_matches(|c| c == '<' || c == '>').to_string();
+            if Some(to.as_str()) == reject {
+                writer.write_all(b"550 no such user\r\n")?;
+            } else {
+                mail.recipients.push(to);
+                writer.write_all(b"250 OK\r\n")?;
+            }
+        } else if command == "DATA" {
+            writer.write_all(b"354 end data with <CR><LF>.<CR><LF>\r\n")?;
+            loop {
+                line.clear();
+                if reader.read_line(&mut line)? == 0 {
+                    return Ok(None);
+                }
+                let data_line = line.trim_end_matches("\r\n");
+                if data_line == "." {
+                    break;
+                }
+                let data_line = data_line.strip_prefix('.').unwrap_or(data_line);
+                mail.data.push_str(data_line);
+                mail.data.push('\n');
+            }
+            line.clear();
+            delivered = true;
+            writer.write_all(b"250 queued\r\n")?;
+        } else if command == "QUIT" {
+            writer.write_all(b"221 bye\r\n")?;
+            break;
+        } else {
+            writer.write_all(b"500 unknown command\r\n")?;
+        }
+    }
+    Ok(if delivered { Some(mail) } else { None })
+}
+
+fn it_writes_one_line_per_message() {
+    let messenger = WriteMessenger::new(Vec::new());
+    let mut tracker = LimitTracker::new(&messenger, 100);
+    tracker.set_value(80);
+    tracker.set_value(100);
+
+    let written = String::from_utf8(messenger.into_inner()).unwrap();
+    assert_eq!(
+        written,
+        "Warning: You've used up over 75% of your quota!\nError: You are over your quota!\n"
+    );
+}
+
+fn it_appends_to_a_log_file() {
+    let dir = TempDir::new("messengers-test").unwrap();
+    let path = dir.path().join("alerts.log");
+
+    FileMessenger::new(&path).send("first");
+    // a second messenger on the same file appends instead of overwriting
+    let messenger = FileMessenger::new(&path);
+    messenger.send("second\nline");
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let messages: Vec<&str> = log
+        .lines()
+        .map(|line| line.split_once(' ').unwrap().1)
+        .collect();
+    assert_eq!(messages, ["first", "second\\nline"]);
+    assert!(log
+        .lines()
+        .all(|line| line.split_once(' ').unwrap().0.parse::<u64>().is_ok()));
+
+    let missing = FileMessenger::new(dir.path().join("no-such-dir/alerts.log"));
+    assert!(missing.try_send("lost").is_err());
+}
+
+fn it_forwards_messages_through_a_channel() {
+    let (messenger, receiver) = ChannelMessenger::new();
+    let consumer = thread::spawn(move || receiver.iter().collect::<Vec<String>>());
+
+    let mut tracker = LimitTracker::new(&messenger, 100);
+    tracker.set_value(75);
+    tracker.set_value(90);
+    drop(tracker);
+    // dropping the sender ends the consumer's iterator
+    drop(messenger);
+    assert_eq!(
+        consumer.join().unwrap(),
+        [
+            "Warning: You've used up over 75% of your quota!",
+            "Urgent warning: You've used up over 90% of your quota!",
+        ]
+    );
+
+    let (messenger, receiver) = ChannelMessenger::new();
+    drop(receiver);
+    assert!(messenger.try_send("nobody listens").is_err());
+}
+
+fn it_sends_mail_through_smtp() {
+    let server = FakeSmtpServer::start(2);
+    let messenger = SmtpMessenger::new(
+        &server.address,
+        "quota@example.com",
+        &["ops@example.com", "owner@example.com"],
+    )
+    .unwrap()
+    .subject("Storage quota")
+    .unwrap();
+
+    messenger.try_send("Warning: 80% used").unwrap();
+    // a line starting with "." must survive dot-stuffing
+    messenger.try_send("first line\n.hidden\n.").unwrap();
+
+    let mails = server.finish();
+    assert_eq!(mails.len(), 2);
+    assert_eq!(mails[0].from, "quota@example.com");
+    assert_eq!(
+        mails[0].recipients,
+        ["ops@example.com", "owner@example.com"]
+    );
+    assert_eq!(
+        mails[0].data,
+        "From: <quota@example.com>\n\
+         To: <ops@example.com>, <owner@example.com>\n\
+         Subject: Storage quota\n\
+         \n\
+         Warning: 80% used\n"
+    );
+    assert!(mails[1].data.ends_with("\nfirst line\n.hidden\n.\n"));
+}
+
+fn it_reports_smtp_rejections() {
+    let server = FakeSmtpServer::start_rejecting(1, Some("nobody@example.com"));
+    let messenger = SmtpMessenger::new(
+        &server.address,
+        "quota@example.com",
+        &["nobody@example.com"],
+    )
+    .unwrap();
+
+    match messenger.try_send("lost") {
+        Err(SmtpError::Rejected { command, reply }) => {
+            assert_eq!(command, "RCPT TO:<nobody@example.com>");
+            assert_eq!(reply, "550 no such user");
+        }
+        other => panic!("expected a rejection, got {:?}", other),
+    }
+    // the client hung up without sending anything
+    drop(messenger);
+    assert!(server.finish().is_empty());
+
+    // nothing listening on the port at all
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    drop(listener);
+    let messenger =
+        SmtpMessenger::new(&address, "quota@example.com", &["ops@example.com"]).unwrap();
+    assert!(matches!(messenger.try_send("lost"), Err(SmtpError::Io(_))));
+}
+
+fn it_rejects_line_breaks_in_addresses_and_subject() {
+    let injected = "quota@example.com>\r\nRCPT TO:<victim@example.com";
+    match SmtpMessenger::new("127.0.0.1:25", injected, &["ops@example.com"]) {
+        Err(SmtpError::LineBreak { field, value }) => {
+            assert_eq!(field, "sender");
+            assert_eq!(value, injected);
+        }
+        other => panic!("expected a line break error, got {:?}", other.err()),
+    }
+    let err = SmtpMessenger::new(
+        "127.0.0.1:25",
+        "quota@example.com",
+        &["ops@example.com", "ops@example.com>\nDATA"],
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "smtp recipient contains a line break: \"ops@example.com>\\nDATA\""
+    );
+
+    let messenger =
+        SmtpMessenger::new("127.0.0.1:25", "quota@example.com", &["ops@example.com"]).unwrap();
+    assert!(matches!(
+        messenger.subject("Alert\r\nBcc: everyone@example.com"),
+        Err(SmtpError::LineBreak {
+            field: "subject",
+            ..
+        })
+    ));
+}
@@ -0,0 +1,501 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+pub fn mock() {
+    // A small mocking toolkit
+
+    // MockMessenger is a hand-written mock for one method of one trait, and all it can tell us
+    // is what was sent. Mock generalises it:
+    //  mock.method::<Args, Return>("name") gives a MockMethod that a trait impl calls with its
+    //  arguments (several arguments go in a tuple), it records every call
+    //  expect(matcher) sets up an expected call, with how many times it should happen (times)
+    //  and what it returns (returning)
+    //  verify() checks every expectation was met and nothing unexpected was called
+    //  assert_calls checks the exact calls, in order, across all methods of the mock
+    // Failures list what was expected next to what happened, as a diff.
+
+    let mock = Mock::new();
+    let ages = FullAgesMock::new(&mock);
+    ages.age
+        .expect(eq(String::from("kartik")))
+        .returning(|_| Some(25));
+    println!("age of kartik: {:?}", ages.age(String::from("kartik")));
+    println!("age of nobody: {:?}", ages.age(String::from("nobody")));
+    if let Err(err) = ages.age.verify() {
+        println!("verify failed:\n{}", err);
+    }
+
+    it_records_calls_and_returns_values();
+    it_checks_call_counts();
+    it_diffs_the_order_of_calls();
+}
+
+// One call as it shows up in the mock's log and in failure messages: name("arg", 1)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub method: &'static str,
+    pub args: String,
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.method, self.args)
+    }
+}
+
+// Every failed check, one per line
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockError(pub Vec<String>);
+
+impl fmt::Display for MockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join("\n"))
+    }
+}
+
+impl Error for MockError {}
+
+// Owns the call log shared by all of its methods, so the order of calls across methods is known
+pub struct Mock {
+    log: Rc<RefCell<Vec<Call>>>,
+}
+
+impl Mock {
+    pub fn new() -> Mock {
+        Mock {
+            log: Rc::new(RefCell::new(Vec::new())),
+        }
+    }
+
+    // A method that returns R::default() unless an expectation says otherwise
+    pub fn method<A, R>(&self, name: &'static str) -> MockMethod<A, R>
+    where
+        A: fmt::Debug,
+        R: Default + 'static,
+    {
+        self.method_returning(name, |_| R::default())
+    }
+
+    // A method that returns fallback(args) unless an expectation says otherwise
+    pub fn method_returning<A, R, F>(&self, name: &'static str, fallback: F) -> MockMethod<A, R>
+    where
+        A: fmt::Debug,
+        F: Fn(&A) -> R + 'static,
+    {
+        MockMethod {
+            name,
+            log: Rc::clone(&self.log),
+            calls: RefCell::new(Vec::new()),
+            expectations: RefCell::new(Vec::new()),
+            unexpected: RefCell::new(Vec::new()),
+            fallback: Rc::new(fallback),
+        }
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.log.borrow().clone()
+    }
+
+    // Compares every call made so far, in order, against the expected name(args) strings
+    pub fn verify_calls(&self, expected: &[&str]) -> Result<(), MockError> {
+        let actual: Vec<String> = self.log.borrow().iter().map(Call::to_string).collect();
+        let expected: Vec<String> = expected.iter().map(|call| call.to_string()).collect();
+        if actual == expected {
+            return Ok(());
+        }
+        let mut report = vec![String::from("calls differ (- expected, + actual):")];
+        report.extend(diff_lines(&expected, &actual));
+        Err(MockError(report))
+    }
+
+    pub fn assert_calls(&self, expected: &[&str]) {
+        if let Err(err) = self.verify_calls(expected) {
+            panic!("{}", err);
+        }
+    }
+}
+
+pub struct Matcher<A> {
+    description: String,
+    test: Box<dyn Fn(&A) -> bool>,
+}
+
+impl<A> Matcher<A> {
+    fn matches(&self, args: &A) -> bool {
+        (self.test)(args)
+    }
+}
+
+pub fn any<A>() -> Matcher<A> {
+    matches("any", |_| true)
+}
+
+pub fn eq<A: PartialEq + fmt::Debug + 'static>(expected: A) -> Matcher<A> {
+    Matcher {
+        description: format!("{:?}", expected),
+        test: Box::new(move |args| *args == expected),
+    }
+}
+
+pub fn contains<A: AsRef<str>>(needle: &str) -> Matcher<A> {
+    let needle = String::from(needle);
+    Matcher {
+        description: format!("containing {:?}", needle),
+        test: Box::new(move |args: &A| args.as_ref().contains(needle.as_str())),
+    }
+}
+
+pub fn matches<A, F: Fn(&A) -> bool + 'static>(description: &str, test: F) -> Matcher<A> {
+    Matcher {
+        description: String::from(description),
+        test: Box::new(test),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Times {
+    Exactly(usize),
+    AtLeast(usize),
+    AtMost(usize),
+}
+
+impl Times {
+    fn allows(&self, count: usize) -> bool {
+        match *self {
+            Times::Exactly(n) => count == n,
+            Times::AtLeast(n) => count >= n,
+            Times::AtMost(n) => count <= n,
+        }
+    }
+
+    // whether one more call would still be within the limit
+    fn has_room(&self, count: usize) -> bool {
+        match *self {
+            Times::Exactly(n) | Times::AtMost(n) => count < n,
+            Times::AtLeast(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for Times {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (how, n) = match *self {
+            Times::Exactly(n) => ("exactly", n),
+            Times::AtLeast(n) => ("at least", n),
+            Times::AtMost(n) => ("at most", n),
+        };
+        write!(f, "{} {} call{}", how, n, if n == 1 { "" } else { "s" })
+    }
+}
+
+// what a call returns, computed from its arguments
+type Returns<A, R> = Rc<dyn Fn(&A) -> R>;
+
+struct Expectation<A, R> {
+    matcher: Matcher<A>,
+    times: Times,
+    returns: Option<Returns<A, R>>,
+    count: usize,
+}
+
+pub struct MockMethod<A, R> {
+    name: &'static str,
+    log: Rc<RefCell<Vec<Call>>>,
+    calls: RefCell<Vec<String>>,
+    expectations: RefCell<Vec<Expectation<A, R>>>,
+    // calls no expectation matched, when there are expectations
+    unexpected: RefCell<Vec<String>>,
+    fallback: Returns<A, R>,
+}
+
+impl<A: fmt::Debug, R> MockMethod<A, R> {
+    // Expects exactly one matching call by default, change it with times()
+    pub fn expect(&self, matcher: Matcher<A>) -> ExpectationHandle<'_, A, R> {
+        let mut expectations = self.expectations.borrow_mut();
+        expectations.push(Expectation {
+            matcher,
+            times: Times::Exactly(1),
+            returns: None,
+            count: 0,
+        });
+        ExpectationHandle {
+            method: self,
+            index: expectations.len() - 1,
+        }
+    }
+
+    // Records the call and returns what the first matching expectation with room left says.
+    // Once every matching expectation has had its calls, the call still counts against the first
+    // one, so verify() reports it as called too often.
+    pub fn call(&self, args: A) -> R {
+        let rendered = format!("{:?}", args);
+        // a single argument is shown as is, a tuple of arguments without its own parentheses
+        let rendered = match rendered.strip_prefix('(') {
+            Some(inner) if rendered.ends_with(",)") => String::from(&inner[..inner.len() - 2]),
+            Some(inner) if rendered.ends_with(')') => String::from(&inner[..inner.len() - 1]),
+            _ => rendered,
+        };
+        self.log.borrow_mut().push(Call {
+            method: self.name,
+            args: rendered.clone(),
+        });
+        self.calls.borrow_mut().push(rendered.clone());
+
+        let returns = {
+            let mut expectations = self.expectations.borrow_mut();
+            let chosen = expectations
+                .iter()
+                .position(|e| e.matcher.matches(&args) && e.times.has_room(e.count))
+                .or_else(|| expectations.iter().position(|e| e.matcher.matches(&args)));
+            match chosen {
+                Some(index) => {
+                    expectations[index].count += 1;
+                    expectations[index].returns.clone()
+                }
+                None => {
+                    if !expectations.is_empty() {
+                        self.unexpected.borrow_mut().push(rendered);
+                    }
+                    None
+                }
+            }
+        };
+        // no borrow is held here, so the closure may call the mock again
+        match returns {
+            Some(returns) => returns(&args),
+            None => (self.fallback)(&args),
+        }
+    }
+
+    // The arguments of every call, as name(args) would show them
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.borrow().clone()
+    }
+
+    pub fn call_count(&self) -> usize {
+        self.calls.borrow().len()
+    }
+
+    pub fn verify(&self) -> Result<(), MockError> {
+        let mut failures = Vec::new();
+        for expectation in self.expectations.borrow().iter() {
+            if !expectation.times.allows(expectation.count) {
+                failures.push(format!(
+                    "{}({}): expected {}, got {}",
+                    self.name,
+                    expectation.matcher.description,
+                    expectation.times,
+                    expectation.count
+                ));
+            }
+        }
+        for args in self.unexpected.borrow().iter() {
+            failures.push(format!("unexpected call {}({})", self.name, args));
+        }
+        if failures.is_empty() {
+            return Ok(());
+        }
+        failures.push(format!("calls to {}:", self.name));
+        for args in self.calls.borrow().iter() {
+            failures.push(format!("  {}({})", self.name, args));
+        }
+        Err(MockError(failures))
+    }
+
+    pub fn assert_verified(&self) {
+        if let Err(err) = self.verify() {
+            panic!("{}", err);
+        }
+    }
+}
+
+// Returned by expect() to adjust the expectation it just added
+pub struct ExpectationHandle<'m, A, R> {
+    method: &'m MockMethod<A, R>,
+    index: usize,
+}
+
+impl<A, R> ExpectationHandle<'_, A, R> {
+    pub fn times(self, times: Times) -> Self {
+        self.method.expectations.borrow_mut()[self.index].times = times;
+        self
+    }
+
+    pub fn returning<F: Fn(&A) -> R + 'static>(self, returns: F) -> Self {
+        self.method.expectations.borrow_mut()[self.index].returns = Some(Rc::new(returns));
+        self
+    }
+}
+
+// A line diff through the longest common subsequence: lines in both are kept with two spaces,
+// lines only expected get "- ", lines only in actual get "+ "
+fn diff_lines(expected: &[String], actual: &[String]) -> Vec<String> {
+    // common[i][j] is the length of the LCS of expected[i..] and actual[j..]
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            lines.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+trait Ages {
+    fn age(&self, name: String) -> Option<u32>;
+    fn set_age(&self, name: &str, age: u32) -> bool;
+}
+
+// Both methods of Ages recording into one log
+struct FullAgesMock {
+    age: MockMethod<String, Option<u32>>,
+    set_age: MockMethod<(String, u32), bool>,
+}
+
+impl FullAgesMock {
+    fn new(mock: &Mock) -> FullAgesMock {
+        FullAgesMock {
+            age: mock.method("age"),
+            set_age: mock.method_returning("set_age", |_| true),
+        }
+    }
+}
+
+impl Ages for FullAgesMock {
+    fn age(&self, name: String) -> Option<u32> {
+        self.age.call(name)
+    }
+
+    fn set_age(&self, name: &str, age: u32) -> bool {
+        self.set_age.call((String::from(name), age))
+    }
+}
+
+// The code under test: bumps an age and returns the new one
+fn have_birthday<T: Ages>(ages: &T, name: &str) -> Option<u32> {
+    let age = ages.age(String::from(name))? + 1;
+    if ages.set_age(name, age) {
+        Some(age)
+    } else {
+        None
+    }
+}
+
+fn it_records_calls_and_returns_values() {
+    let mock = Mock::new();
+    let ages = FullAgesMock::new(&mock);
+    ages.age
+        .expect(eq(String::from("kartik")))
+        .returning(|_| Some(25));
+    ages.set_age
+        .expect(matches("kartik, over 18", |(name, age): &(String, u32)| {
+            name == "kartik" && *age > 18
+        }))
+        .returning(|_| true);
+
+    assert_eq!(have_birthday(&ages, "kartik"), Some(26));
+    ages.age.assert_verified();
+    ages.set_age.assert_verified();
+    assert_eq!(ages.set_age.calls(), [r#""kartik", 26"#]);
+
+    // no expectations: the mock only records, and returns the fallback
+    let mock = Mock::new();
+    let ages = FullAgesMock::new(&mock);
+    assert_eq!(have_birthday(&ages, "nobody"), None);
+    assert_eq!(ages.age.call_count(), 1);
+    assert_eq!(ages.set_age.call_count(), 0);
+    assert!(ages.age.verify().is_ok());
+}
+
+fn it_checks_call_counts() {
+    let mock = Mock::new();
+    let ages = FullAgesMock::new(&mock);
+    ages.age
+        .expect(contains("a"))
+        .times(Times::AtLeast(2))
+        .returning(|_| Some(1));
+    ages.age
+        .expect(eq(String::from("bob")))
+        .times(Times::AtMost(1));
+
+    ages.age(String::from("anna"));
+    ages.age(String::from("bob"));
+    ages.age(String::from("bob"));
+    ages.age(String::from("chris"));
+
+    let err = ages.age.verify().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        [
+            r#"age(containing "a"): expected at least 2 calls, got 1"#,
+            r#"age("bob"): expected at most 1 call, got 2"#,
+            r#"unexpected call age("chris")"#,
+            "calls to age:",
+            r#"  age("anna")"#,
+            r#"  age("bob")"#,
+            r#"  age("bob")"#,
+            r#"  age("chris")"#,
+        ]
+        .join("\n")
+    );
+
+    // a second matching expectation picks up once the first has had its calls
+    let mock = Mock::new();
+    let ages = FullAgesMock::new(&mock);
+    ages.age.expect(any()).returning(|_| Some(1));
+    ages.age.expect(any()).returning(|_| Some(2));
+    assert_eq!(ages.age(String::from("x")), Some(1));
+    assert_eq!(ages.age(String::from("x")), Some(2));
+    ages.age.assert_verified();
+}
+
+fn it_diffs_the_order_of_calls() {
+    let mock = Mock::new();
+    let ages = FullAgesMock::new(&mock);
+    ages.age.expect(any()).returning(|_| Some(30));
+    have_birthday(&ages, "kartik");
+
+    mock.assert_calls(&[r#"age("kartik")"#, r#"set_age("kartik", 31)"#]);
+    assert_eq!(mock.calls()[1].method, "set_age");
+
+    let err = mock
+        .verify_calls(&[
+            r#"age("kartik")"#,
+            r#"age("kartik")"#,
+            r#"set_age("kartik", 30)"#,
+        ])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        [
+            "calls differ (- expected, + actual):",
+            r#"  age("kartik")"#,
+            r#"- age("kartik")"#,
+            r#"- set_age("kartik", 30)"#,
+            r#"+ set_age("kartik", 31)"#,
+        ]
+        .join("\n")
+    );
+}
@@ -1 +1,13 @@
-pub mod smart_pointers;
\ No newline at end of file
+pub mod arena;
+pub mod async_messengers;
+pub mod drop_tracer;
+pub mod leak_detector;
+pub mod messengers;
+pub mod mock;
+pub mod my_rc;
+pub mod my_ref_cell;
+pub mod quota_service;
+pub mod rate_limits;
+pub mod scope_guards;
+pub mod smart_pointers;
+pub mod weak_pointers;
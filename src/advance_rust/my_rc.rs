@@ -0,0 +1,400 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+
+pub fn my_rc() {
+    // Building Rc<T> and Weak<T> ourselves
+
+    // MyBox<T> showed that a smart pointer is a struct with Deref (and usually Drop).
+    // Rc<T> is the same idea with one heap allocation shared by every clone. Next to the value the
+    // allocation holds two counters:
+    //  strong: how many MyRc point at it, the value is dropped when this reaches 0
+    //  weak: how many MyWeak point at it, plus 1 shared by all the strong pointers together.
+    //        The allocation itself is freed when this reaches 0.
+    // Keeping the value and the allocation apart is what lets a MyWeak outlive the value: upgrade()
+    // still has counters to look at, it just finds strong == 0 and returns None.
+
+    let a = MyRc::new(String::from("shared"));
+    let b = a.clone();
+    let weak = MyRc::downgrade(&a);
+    println!(
+        "a = {}, strong = {}, weak = {}",
+        a,
+        MyRc::strong_count(&a),
+        MyRc::weak_count(&a)
+    );
+    drop(a);
+    println!("after dropping a, b still sees {}", b);
+    drop(b);
+    println!("after dropping b, upgrade = {:?}", weak.upgrade());
+
+    // make_mut is copy-on-write: it only clones the value when someone else can see it
+    let mut first = MyRc::new(vec![1, 2, 3]);
+    let second = first.clone();
+    MyRc::make_mut(&mut first).push(4);
+    println!("first = {:?}, second = {:?}", first, second);
+
+    it_counts_strong_and_weak_references();
+    it_drops_the_value_before_the_allocation();
+    it_unwraps_only_the_last_strong_reference();
+    it_copies_on_write_with_make_mut();
+    it_detaches_weak_references_in_make_mut();
+}
+
+struct RcBox<T> {
+    strong: Cell<usize>,
+    weak: Cell<usize>,
+    // dropped by hand when strong reaches 0, which can be long before the RcBox itself is freed
+    value: ManuallyDrop<T>,
+}
+
+pub struct MyRc<T> {
+    ptr: NonNull<RcBox<T>>,
+    // tells the drop checker that dropping a MyRc<T> may drop a T
+    marker: PhantomData<RcBox<T>>,
+}
+
+pub struct MyWeak<T> {
+    ptr: NonNull<RcBox<T>>,
+}
+
+thread_local! {
+    // number of RcBox allocations not yet freed, so the tests can see the allocation go away
+    static LIVE_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn live_allocations() -> usize {
+    LIVE_ALLOCATIONS.with(|live| live.get())
+}
+
+impl<T> RcBox<T> {
+    // Called when the weak count (including the strong pointers' shared one) reaches 0
+    unsafe fn free(ptr: NonNull<RcBox<T>>) {
+        // value is ManuallyDrop, so this only frees the memory
+        drop(Box::from_raw(ptr.as_ptr()));
+        LIVE_ALLOCATIONS.with(|live| live.set(live.get() - 1));
+    }
+}
+
+impl<T> MyRc<T> {
+    pub fn new(value: T) -> MyRc<T> {
+        let boxed = Box::new(RcBox {
+            strong: Cell::new(1),
+            weak: Cell::new(1),
+            value: ManuallyDrop::new(value),
+        });
+        LIVE_ALLOCATIONS.with(|live| live.set(live.get() + 1));
+        MyRc {
+            // Box::into_raw never returns null
+            ptr: unsafe { NonNull::new_unchecked(Box::into_raw(boxed)) },
+            marker: PhantomData,
+        }
+    }
+
+    fn inner(&self) -> &RcBox<T> {
+        // the allocation lives at least as long as any MyRc pointing at it
+        unsafe { self.ptr.as_ref() }
+    }
+
+    pub fn strong_count(this: &MyRc<T>) -> usize {
+        this.inner().strong.get()
+    }
+
+    // without the one weak reference the strong pointers share
+    pub fn weak_count(this: &MyRc<T>) -> usize {
+        this.inner().weak.get() - 1
+    }
+
+    pub fn ptr_eq(this: &MyRc<T>, other: &MyRc<T>) -> bool {
+        this.ptr == other.ptr
+    }
+
+    pub fn downgrade(this: &MyRc<T>) -> MyWeak<T> {
+        let inner = this.inner();
+        inner.weak.set(inner.weak.get() + 1);
+        MyWeak { ptr: this.ptr }
+    }
+
+    // Gives the value back if this is the only strong reference, otherwise returns the MyRc unchanged
+    pub fn try_unwrap(this: MyRc<T>) -> Result<T, MyRc<T>> {
+        if MyRc::strong_count(&this) != 1 {
+            return Err(this);
+        }
+        let this = ManuallyDrop::new(this);
+        let inner = this.inner();
+        inner.strong.set(0);
+        // strong is 0 so nothing will drop the value again, we own it now
+        let value = unsafe { ptr::read(&*inner.value) };
+        let weak = inner.weak.get() - 1;
+        inner.weak.set(weak);
+        if weak == 0 {
+            unsafe { RcBox::free(this.ptr) };
+        }
+        Ok(value)
+    }
+
+    // Mutable access when no other MyRc or MyWeak can observe the value
+    pub fn get_mut(this: &mut MyRc<T>) -> Option<&mut T> {
+        if MyRc::strong_count(this) == 1 && MyRc::weak_count(this) == 0 {
+            Some(unsafe { &mut (*this.ptr.as_ptr()).value })
+        } else {
+            None
+        }
+    }
+
+    // Copy-on-write: clones the value into a new allocation if other MyRc share it.
+    // If only MyWeak share it, the value is moved out instead and those MyWeak stop upgrading.
+    pub fn make_mut(this: &mut MyRc<T>) -> &mut T
+    where
+        T: Clone,
+    {
+        if MyRc::strong_count(this) != 1 {
+            *this = MyRc::new((**this).clone());
+        } else if MyRc::weak_count(this) != 0 {
+            let inner = this.inner();
+            inner.strong.set(0);
+            let value = unsafe { ptr::read(&*inner.value) };
+            // the MyWeak still hold the old allocation, so the weak count cannot reach 0 here
+            inner.weak.set(inner.weak.get() - 1);
+            // overwrite without running Drop, the old allocation was already let go of by hand
+            unsafe { ptr::write(this, MyRc::new(value)) };
+        }
+        unsafe { &mut (*this.ptr.as_ptr()).value }
+    }
+}
+
+impl<T> Clone for MyRc<T> {
+    fn clone(&self) -> MyRc<T> {
+        let inner = self.inner();
+        inner.strong.set(inner.strong.get() + 1);
+        MyRc {
+            ptr: self.ptr,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for MyRc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner().value
+    }
+}
+
+impl<T> Drop for MyRc<T> {
+    fn drop(&mut self) {
+        let inner = self.inner();
+        let strong = inner.strong.get() - 1;
+        inner.strong.set(strong);
+        if strong != 0 {
+            return;
+        }
+        // last strong reference: drop the value now, the allocation waits for the MyWeak
+        unsafe { ManuallyDrop::drop(&mut (*self.ptr.as_ptr()).value) };
+        let inner = self.inner();
+        let weak = inner.weak.get() - 1;
+        inner.weak.set(weak);
+        if weak == 0 {
+            unsafe { RcBox::free(self.ptr) };
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for MyRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyRc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> MyWeak<T> {
+    fn inner(&self) -> &RcBox<T> {
+        // a MyWeak keeps the allocation (not the value) alive
+        unsafe { self.ptr.as_ref() }
+    }
+
+    pub fn upgrade(&self) -> Option<MyRc<T>> {
+        let inner = self.inner();
+        let strong = inner.strong.get();
+        if strong == 0 {
+            return None;
+        }
+        inner.strong.set(strong + 1);
+        Some(MyRc {
+            ptr: self.ptr,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn strong_count(&self) -> usize {
+        self.inner().strong.get()
+    }
+}
+
+impl<T> Clone for MyWeak<T> {
+    fn clone(&self) -> MyWeak<T> {
+        let inner = self.inner();
+        inner.weak.set(inner.weak.get() + 1);
+        MyWeak { ptr: self.ptr }
+    }
+}
+
+impl<T> Drop for MyWeak<T> {
+    fn drop(&mut self) {
+        let inner = self.inner();
+        let weak = inner.weak.get() - 1;
+        inner.weak.set(weak);
+        if weak == 0 {
+            unsafe { RcBox::free(self.ptr) };
+        }
+    }
+}
+
+// Like CustomPointer, but instead of printing it writes its data into a shared log when dropped
+#[derive(Clone)]
+struct CustomPointer {
+    data: String,
+    drops: Rc<RefCell<Vec<String>>>,
+}
+
+impl CustomPointer {
+    fn new(data: &str, drops: &Rc<RefCell<Vec<String>>>) -> CustomPointer {
+        CustomPointer {
+            data: String::from(data),
+            drops: Rc::clone(drops),
+        }
+    }
+}
+
+impl Drop for CustomPointer {
+    fn drop(&mut self) {
+        self.drops.borrow_mut().push(self.data.clone());
+    }
+}
+
+fn it_counts_strong_and_weak_references() {
+    let a = MyRc::new(5);
+    assert_eq!((MyRc::strong_count(&a), MyRc::weak_count(&a)), (1, 0));
+
+    let b = a.clone();
+    let weak = MyRc::downgrade(&a);
+    let weak2 = weak.clone();
+    assert!(MyRc::ptr_eq(&a, &b));
+    assert_eq!((MyRc::strong_count(&a), MyRc::weak_count(&a)), (2, 2));
+
+    let c = weak.upgrade().unwrap();
+    assert_eq!(*c, 5);
+    assert_eq!(MyRc::strong_count(&a), 3);
+
+    drop((b, c, weak2));
+    assert_eq!((MyRc::strong_count(&a), MyRc::weak_count(&a)), (1, 1));
+    assert_eq!(weak.strong_count(), 1);
+}
+
+fn it_drops_the_value_before_the_allocation() {
+    let drops = Rc::new(RefCell::new(Vec::new()));
+    let allocations = live_allocations();
+
+    let a = MyRc::new(CustomPointer::new("a", &drops));
+    let b = a.clone();
+    let weak = MyRc::downgrade(&a);
+    assert_eq!(live_allocations(), allocations + 1);
+
+    drop(a);
+    assert!(drops.borrow().is_empty());
+
+    // the last strong reference drops the value, the weak one keeps the allocation around
+    drop(b);
+    assert_eq!(*drops.borrow(), ["a"]);
+    assert_eq!(live_allocations(), allocations + 1);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(weak.strong_count(), 0);
+
+    drop(weak);
+    assert_eq!(live_allocations(), allocations);
+    assert_eq!(*drops.borrow(), ["a"]);
+
+    // without any MyWeak both go at once
+    drop(MyRc::new(CustomPointer::new("b", &drops)));
+    assert_eq!(*drops.borrow(), ["a", "b"]);
+    assert_eq!(live_allocations(), allocations);
+}
+
+fn it_unwraps_only_the_last_strong_reference() {
+    let drops = Rc::new(RefCell::new(Vec::new()));
+    let allocations = live_allocations();
+
+    let a = MyRc::new(CustomPointer::new("a", &drops));
+    let b = a.clone();
+    let a = MyRc::try_unwrap(a).err().unwrap();
+    assert_eq!(MyRc::strong_count(&a), 2);
+    drop(b);
+
+    let weak = MyRc::downgrade(&a);
+    let value = MyRc::try_unwrap(a).ok().unwrap();
+    assert_eq!(value.data, "a");
+    assert!(weak.upgrade().is_none());
+    // the value moved out, nothing was dropped
+    assert!(drops.borrow().is_empty());
+    drop(weak);
+    assert_eq!(live_allocations(), allocations);
+
+    drop(value);
+    assert_eq!(*drops.borrow(), ["a"]);
+}
+
+fn it_copies_on_write_with_make_mut() {
+    let drops = Rc::new(RefCell::new(Vec::new()));
+
+    let mut a = MyRc::new(CustomPointer::new("a", &drops));
+    let b = a.clone();
+    assert!(MyRc::get_mut(&mut a).is_none());
+
+    // shared: a gets its own copy, b keeps the original
+    MyRc::make_mut(&mut a).data.push_str(" changed");
+    assert!(!MyRc::ptr_eq(&a, &b));
+    assert_eq!((a.data.as_str(), b.data.as_str()), ("a changed", "a"));
+    assert_eq!(MyRc::strong_count(&b), 1);
+
+    // not shared: changed in place
+    let before = a.ptr;
+    MyRc::make_mut(&mut a).data.push_str(" again");
+    assert_eq!(a.ptr, before);
+    MyRc::get_mut(&mut a).unwrap().data.push('!');
+    assert_eq!(a.data, "a changed again!");
+
+    drop(a);
+    drop(b);
+    assert_eq!(*drops.borrow(), ["a changed again!", "a"]);
+}
+
+fn it_detaches_weak_references_in_make_mut() {
+    let drops = Rc::new(RefCell::new(Vec::new()));
+    let allocations = live_allocations();
+
+    let mut a = MyRc::new(CustomPointer::new("a", &drops));
+    let weak = MyRc::downgrade(&a);
+    MyRc::make_mut(&mut a).data.push_str(" moved");
+
+    // the value moved to a new allocation without being cloned or dropped
+    assert!(weak.upgrade().is_none());
+    assert!(drops.borrow().is_empty());
+    assert_eq!(live_allocations(), allocations + 2);
+    assert_eq!(MyRc::weak_count(&a), 0);
+
+    drop(weak);
+    assert_eq!(live_allocations(), allocations + 1);
+    drop(a);
+    assert_eq!(*drops.borrow(), ["a moved"]);
+    assert_eq!(live_allocations(), allocations);
+}
@@ -0,0 +1,378 @@
+use std::cell::{Cell, UnsafeCell};
+use std::error::Error;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::panic::Location;
+
+use crate::test_support::catch_quiet_panic;
+
+pub fn my_ref_cell() {
+    // Building RefCell<T> ourselves
+
+    // RefCell<T> moves the borrowing rules from compile time to runtime. It keeps one counter next
+    // to the value:
+    //  0   nobody is borrowing
+    //  n   n shared borrows (Ref) are alive
+    //  -1  one mutable borrow (RefMut) is alive
+    // borrow() and borrow_mut() check the counter, and the Ref/RefMut guards they return put it back
+    // when they are dropped. A borrow that breaks the rules panics, try_borrow and try_borrow_mut
+    // return an error instead.
+
+    // The panic message of std's RefCell only says "already borrowed", which is not much help when
+    // the other borrow was taken somewhere else entirely. Build with
+    //   cargo run --features debug-refcell
+    // and MyRefCell also remembers where every outstanding borrow was taken (#[track_caller] gives
+    // us the caller's location), so the error points at them.
+
+    let cell = MyRefCell::new(vec![1, 2, 3]);
+    {
+        let first = cell.borrow();
+        let second = cell.borrow();
+        println!("two shared borrows: {:?} {:?}", *first, *second);
+    }
+    cell.borrow_mut().push(4);
+
+    let guard = cell.borrow_mut();
+    match cell.try_borrow() {
+        Ok(_) => unreachable!(),
+        Err(err) => println!("try_borrow while mutably borrowed: {}", err),
+    }
+    drop(guard);
+    println!(
+        "after the guard is dropped: {:?}",
+        *cell.try_borrow().unwrap()
+    );
+
+    it_allows_many_readers_or_one_writer();
+    it_panics_on_conflicting_borrows();
+    it_reports_where_the_outstanding_borrow_was_taken();
+}
+
+// Number of Ref alive, or MUTABLY_BORROWED while a RefMut is alive
+type BorrowFlag = isize;
+const UNUSED: BorrowFlag = 0;
+const MUTABLY_BORROWED: BorrowFlag = -1;
+
+pub struct MyRefCell<T> {
+    borrow: Cell<BorrowFlag>,
+    // where each live borrow was taken, oldest first. A dropped guard leaves a None behind until the
+    // guards after it are gone too, so every guard can keep the index of its own entry.
+    #[cfg(feature = "debug-refcell")]
+    borrowed_at: Cell<Vec<Option<&'static Location<'static>>>>,
+    value: UnsafeCell<T>,
+}
+
+#[derive(Debug)]
+pub struct BorrowError {
+    location: Option<&'static Location<'static>>,
+}
+
+#[derive(Debug)]
+pub struct BorrowMutError {
+    // every live borrow, there can be many readers
+    locations: Vec<&'static Location<'static>>,
+}
+
+impl fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "already mutably borrowed")?;
+        if let Some(location) = self.location {
+            write!(f, " (mutable borrow taken at {})", location)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BorrowMutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "already borrowed")?;
+        match self.locations.as_slice() {
+            [] => {}
+            [location] => write!(f, " (outstanding borrow taken at {})", location)?,
+            locations => {
+                let locations: Vec<String> = locations.iter().map(|l| l.to_string()).collect();
+                write!(
+                    f,
+                    " (outstanding borrows taken at {})",
+                    locations.join(", ")
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for BorrowError {}
+
+impl Error for BorrowMutError {}
+
+impl<T> MyRefCell<T> {
+    pub fn new(value: T) -> MyRefCell<T> {
+        MyRefCell {
+            borrow: Cell::new(UNUSED),
+            #[cfg(feature = "debug-refcell")]
+            borrowed_at: Cell::new(Vec::new()),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    // &mut self already proves nobody else is borrowing, no runtime check needed
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    #[track_caller]
+    pub fn borrow(&self) -> Ref<'_, T> {
+        match self.try_borrow() {
+            Ok(guard) => guard,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[track_caller]
+    pub fn borrow_mut(&self) -> RefMut<'_, T> {
+        match self.try_borrow_mut() {
+            Ok(guard) => guard,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[track_caller]
+    pub fn try_borrow(&self) -> Result<Ref<'_, T>, BorrowError> {
+        let borrow = self.borrow.get();
+        if borrow == MUTABLY_BORROWED {
+            return Err(BorrowError {
+                location: self.borrowed_at().first().copied(),
+            });
+        }
+        self.borrow.set(borrow + 1);
+        Ok(Ref {
+            // no RefMut exists, and none can be created while this Ref is alive
+            value: unsafe { &*self.value.get() },
+            borrow: &self.borrow,
+            #[cfg(feature = "debug-refcell")]
+            _location: self.record_location(),
+        })
+    }
+
+    #[track_caller]
+    pub fn try_borrow_mut(&self) -> Result<RefMut<'_, T>, BorrowMutError> {
+        if self.borrow.get() != UNUSED {
+            return Err(BorrowMutError {
+                locations: self.borrowed_at(),
+            });
+        }
+        self.borrow.set(MUTABLY_BORROWED);
+        Ok(RefMut {
+            // nobody else is borrowing, and nobody can while this RefMut is alive
+            value: unsafe { &mut *self.value.get() },
+            borrow: &self.borrow,
+            #[cfg(feature = "debug-refcell")]
+            _location: self.record_location(),
+        })
+    }
+
+    #[cfg(feature = "debug-refcell")]
+    #[track_caller]
+    fn record_location(&self) -> LocationEntry<'_> {
+        let mut locations = self.borrowed_at.take();
+        locations.push(Some(Location::caller()));
+        let index = locations.len() - 1;
+        self.borrowed_at.set(locations);
+        LocationEntry {
+            locations: &self.borrowed_at,
+            index,
+        }
+    }
+
+    #[cfg(feature = "debug-refcell")]
+    fn borrowed_at(&self) -> Vec<&'static Location<'static>> {
+        let locations = self.borrowed_at.take();
+        let live = locations.iter().flatten().copied().collect();
+        self.borrowed_at.set(locations);
+        live
+    }
+
+    #[cfg(not(feature = "debug-refcell"))]
+    fn borrowed_at(&self) -> Vec<&'static Location<'static>> {
+        Vec::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyRefCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_borrow() {
+            Ok(value) => f.debug_struct("MyRefCell").field("value", &*value).finish(),
+            Err(_) => f.write_str("MyRefCell { <borrowed> }"),
+        }
+    }
+}
+
+// A guard's entry in MyRefCell::borrowed_at, cleared when the guard is dropped
+#[cfg(feature = "debug-refcell")]
+struct LocationEntry<'b> {
+    locations: &'b Cell<Vec<Option<&'static Location<'static>>>>,
+    index: usize,
+}
+
+#[cfg(feature = "debug-refcell")]
+impl Drop for LocationEntry<'_> {
+    fn drop(&mut self) {
+        let mut locations = self.locations.take();
+        locations[self.index] = None;
+        while locations.last() == Some(&None) {
+            locations.pop();
+        }
+        self.locations.set(locations);
+    }
+}
+
+pub struct Ref<'b, T> {
+    value: &'b T,
+    borrow: &'b Cell<BorrowFlag>,
+    // only here to be dropped with the guard
+    #[cfg(feature = "debug-refcell")]
+    _location: LocationEntry<'b>,
+}
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> Drop for Ref<'_, T> {
+    fn drop(&mut self) {
+        self.borrow.set(self.borrow.get() - 1);
+    }
+}
+
+pub struct RefMut<'b, T> {
+    value: &'b mut T,
+    borrow: &'b Cell<BorrowFlag>,
+    // only here to be dropped with the guard
+    #[cfg(feature = "debug-refcell")]
+    _location: LocationEntry<'b>,
+}
+
+impl<T> Deref for RefMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for RefMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T> Drop for RefMut<'_, T> {
+    fn drop(&mut self) {
+        self.borrow.set(UNUSED);
+    }
+}
+
+fn it_allows_many_readers_or_one_writer() {
+    let mut cell = MyRefCell::new(String::from("hello"));
+    {
+        let first = cell.borrow();
+        let second = cell.try_borrow().unwrap();
+        assert_eq!((first.as_str(), second.as_str()), ("hello", "hello"));
+        assert!(cell.try_borrow_mut().is_err());
+        drop(first);
+        // still one reader left
+        assert!(cell.try_borrow_mut().is_err());
+    }
+    {
+        let mut writer = cell.try_borrow_mut().unwrap();
+        writer.push_str(" world");
+        assert!(cell.try_borrow().is_err());
+        assert!(cell.try_borrow_mut().is_err());
+        assert_eq!(format!("{:?}", cell), "MyRefCell { <borrowed> }");
+    }
+    assert_eq!(
+        format!("{:?}", cell),
+        r#"MyRefCell { value: "hello world" }"#
+    );
+
+    cell.get_mut().push('!');
+    assert_eq!(cell.into_inner(), "hello world!");
+}
+
+fn it_panics_on_conflicting_borrows() {
+    let cell = MyRefCell::new(5);
+
+    let message = catch_quiet_panic(|| {
+        let _reader = cell.borrow();
+        let _writer = cell.borrow_mut();
+    });
+    assert!(message.unwrap().starts_with("already borrowed"));
+
+    let message = catch_quiet_panic(|| {
+        let _writer = cell.borrow_mut();
+        let _reader = cell.borrow();
+    });
+    assert!(message.unwrap().starts_with("already mutably borrowed"));
+
+    // the guards were dropped while unwinding, so the cell is usable again
+    *cell.borrow_mut() += 1;
+    assert_eq!(*cell.borrow(), 6);
+}
+
+fn it_reports_where_the_outstanding_borrow_was_taken() {
+    let cell = MyRefCell::new(0);
+    let writer = cell.borrow_mut();
+    let line = line!() - 1;
+    let err = cell.try_borrow().err().unwrap();
+    drop(writer);
+
+    if cfg!(feature = "debug-refcell") {
+        let expected = format!(
+            "already mutably borrowed (mutable borrow taken at {}:{}:23)",
+            file!(),
+            line
+        );
+        assert_eq!(err.to_string(), expected);
+    } else {
+        assert_eq!(err.to_string(), "already mutably borrowed");
+    }
+
+    // with several readers every live one is named, a reader that is gone isn't
+    let first = cell.borrow();
+    let first_line = line!() - 1;
+    let second = cell.borrow();
+    let second_line = line!() - 1;
+    let both = cell.try_borrow_mut().err().unwrap();
+    drop(first);
+    let only_second = cell.try_borrow_mut().err().unwrap();
+    drop(second);
+    assert!(cell.try_borrow_mut().is_ok());
+
+    if cfg!(feature = "debug-refcell") {
+        let expected = format!(
+            "already borrowed (outstanding borrows taken at {0}:{1}:22, {0}:{2}:23)",
+            file!(),
+            first_line,
+            second_line
+        );
+        assert_eq!(both.to_string(), expected);
+        let expected = format!(
+            "already borrowed (outstanding borrow taken at {}:{}:23)",
+            file!(),
+            second_line
+        );
+        assert_eq!(only_second.to_string(), expected);
+    } else {
+        assert_eq!(both.to_string(), "already borrowed");
+        assert_eq!(only_second.to_string(), "already borrowed");
+    }
+}
@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+
+use crate::advance_rust::messengers::ChannelMessenger;
+use crate::advance_rust::smart_pointers::{
+    default_thresholds, Messenger, Threshold, ThresholdAlerts,
+};
+
+pub fn quota_service() {
+    // Sharing a LimitTracker between threads
+
+    // LimitTracker borrows its messenger (&'a T) and needs &mut self to change the value, so only
+    // one thread can ever use it. QuotaService is the same idea for many tenants at once:
+    //  the service is shared with Arc and every method takes &self
+    //  the tenant table is an RwLock<HashMap>, lookups from many threads only take the read lock
+    //  every tenant has its own Mutex, so threads working on different tenants never wait for each other
+    //  counters that are only ever added to are atomics, no lock needed
+    //  the messenger is owned by the service and has to be Send + Sync, because any thread can send
+    // MockMessenger (RefCell) and WriteMessenger aren't Sync, ChannelMessenger, FileMessenger and
+    // SmtpMessenger are.
+
+    let (messenger, alerts) = ChannelMessenger::new();
+    let service = Arc::new(QuotaService::new(messenger));
+    service.add_tenant("acme", 1000).unwrap();
+    service.add_tenant("globex", 500).unwrap();
+
+    let workers: Vec<_> = ["acme", "globex"]
+        .into_iter()
+        .map(|tenant| {
+            let service = Arc::clone(&service);
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    service.record(tenant, 5).unwrap();
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    for snapshot in service.snapshots() {
+        println!("{}", snapshot);
+    }
+    for alert in alerts.try_iter() {
+        println!("alert: {}", alert);
+    }
+
+    it_tracks_tenants_separately();
+    it_rejects_unknown_and_duplicate_tenants();
+    it_survives_many_threads_hammering_it();
+    it_keeps_a_tenant_working_after_an_oversized_record();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuotaError {
+    UnknownTenant(String),
+    TenantExists(String),
+    // recording amount would take the tenant's usage past usize::MAX, nothing was recorded
+    UsageOverflow { tenant: String, amount: usize },
+}
+
+impl fmt::Display for QuotaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuotaError::UnknownTenant(tenant) => write!(f, "no tenant named {}", tenant),
+            QuotaError::TenantExists(tenant) => write!(f, "tenant {} already exists", tenant),
+            QuotaError::UsageOverflow { tenant, amount } => {
+                write!(
+                    f,
+                    "recording {} would overflow the usage of {}",
+                    amount, tenant
+                )
+            }
+        }
+    }
+}
+
+impl Error for QuotaError {}
+
+// A tenant's usage at one moment, copied out so no lock is held while it is looked at
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageSnapshot {
+    pub tenant: String,
+    pub used: usize,
+    pub max: usize,
+    // the highest threshold crossed, in percent
+    pub threshold: Option<f64>,
+}
+
+impl UsageSnapshot {
+    pub fn percent(&self) -> f64 {
+        self.used as f64 / self.max as f64 * 100.0
+    }
+}
+
+impl fmt::Display for UsageSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}/{} ({:.1}%)",
+            self.tenant,
+            self.used,
+            self.max,
+            self.percent()
+        )
+    }
+}
+
+struct TenantUsage {
+    used: usize,
+    max: usize,
+    alerts: ThresholdAlerts,
+}
+
+pub struct QuotaService<M: Messenger + Send + Sync> {
+    messenger: M,
+    thresholds: Vec<Threshold>,
+    tenants: RwLock<HashMap<String, Arc<Mutex<TenantUsage>>>>,
+    // total number of record() calls and alerts sent, across all tenants
+    records: AtomicU64,
+    alerts_sent: AtomicU64,
+}
+
+impl<M: Messenger + Send + Sync> QuotaService<M> {
+    pub fn new(messenger: M) -> QuotaService<M> {
+        QuotaService::with_thresholds(messenger, default_thresholds())
+    }
+
+    pub fn with_thresholds(messenger: M, thresholds: Vec<Threshold>) -> QuotaService<M> {
+        QuotaService {
+            messenger,
+            thresholds,
+            tenants: RwLock::new(HashMap::new()),
+            records: AtomicU64::new(0),
+            alerts_sent: AtomicU64::new(0),
+        }
+    }
+
+    pub fn add_tenant(&self, tenant: &str, max: usize) -> Result<(), QuotaError> {
+        let mut tenants = self.tenants.write().unwrap();
+        if tenants.contains_key(tenant) {
+            return Err(QuotaError::TenantExists(String::from(tenant)));
+        }
+        let usage = TenantUsage {
+            used: 0,
+            max,
+            alerts: ThresholdAlerts::new(self.thresholds.clone()),
+        };
+        tenants.insert(String::from(tenant), Arc::new(Mutex::new(usage)));
+        Ok(())
+    }
+
+    pub fn remove_tenant(&self, tenant: &str) -> Result<(), QuotaError> {
+        match self.tenants.write().unwrap().remove(tenant) {
+            Some(_) => Ok(()),
+            None => Err(QuotaError::UnknownTenant(String::from(tenant))),
+        }
+    }
+
+    // Clones the tenant's Arc so the table lock is only held for the lookup
+    fn tenant(&self, tenant: &str) -> Result<Arc<Mutex<TenantUsage>>, QuotaError> {
+        self.tenants
+            .read()
+            .unwrap()
+            .get(tenant)
+            .cloned()
+            .ok_or_else(|| QuotaError::UnknownTenant(String::from(tenant)))
+    }
+
+    // Adds amount to the tenant's usage and returns the new usage. checked_add, because a panic
+    // while the tenant's lock is held would poison it and every later call for the tenant would panic.
+    pub fn record(&self, tenant: &str, amount: usize) -> Result<usize, QuotaError> {
+        self.records.fetch_add(1, Ordering::Relaxed);
+        let usage = self.tenant(tenant)?;
+        let mut usage = usage.lock().unwrap();
+        let used = usage
+            .used
+            .checked_add(amount)
+            .ok_or_else(|| QuotaError::UsageOverflow {
+                tenant: String::from(tenant),
+                amount,
+            })?;
+        self.update(tenant, &mut usage, used);
+        Ok(used)
+    }
+
+    pub fn set_usage(&self, tenant: &str, used: usize) -> Result<(), QuotaError> {
+        let usage = self.tenant(tenant)?;
+        let mut usage = usage.lock().unwrap();
+        self.update(tenant, &mut usage, used);
+        Ok(())
+    }
+
+    // Back to zero with every threshold re-armed, e.g. at the start of a billing period
+    pub fn reset(&self, tenant: &str) -> Result<(), QuotaError> {
+        let usage = self.tenant(tenant)?;
+        let mut usage = usage.lock().unwrap();
+        usage.used = 0;
+        usage.alerts.reset();
+        Ok(())
+    }
+
+    // The message is sent with the tenant's lock held, which keeps one tenant's alerts in order.
+    // Other tenants are not held up by it.
+    fn update(&self, tenant: &str, usage: &mut TenantUsage, used: usize) {
+        usage.used = used;
+        let percentage_of_max = used as f64 / usage.max as f64 * 100.0;
+        if let Some(threshold) = usage.alerts.update(percentage_of_max) {
+            self.alerts_sent.fetch_add(1, Ordering::Relaxed);
+            self.messenger
+                .send(&format!("[{}] {}", tenant, threshold.message));
+        }
+    }
+
+    pub fn snapshot(&self, tenant: &str) -> Result<UsageSnapshot, QuotaError> {
+        let usage = self.tenant(tenant)?;
+        let usage = usage.lock().unwrap();
+        Ok(UsageSnapshot {
+            tenant: String::from(tenant),
+            used: usage.used,
+            max: usage.max,
+            threshold: usage.alerts.current().map(|threshold| threshold.percent),
+        })
+    }
+
+    // Every tenant, sorted by name. Each snapshot is consistent on its own, but tenants can change
+    // between one snapshot and the next.
+    pub fn snapshots(&self) -> Vec<UsageSnapshot> {
+        let mut names: Vec<String> = self.tenants.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+            .iter()
+            .filter_map(|name| self.snapshot(name).ok())
+            .collect()
+    }
+
+    pub fn records(&self) -> u64 {
+        self.records.load(Ordering::Relaxed)
+    }
+
+    pub fn alerts_sent(&self) -> u64 {
+        self.alerts_sent.load(Ordering::Relaxed)
+    }
+}
+
+// MockMessenger with a Mutex instead of a RefCell, so it can be shared between threads
+struct RecordingMessenger {
+    sent_messages: Mutex<Vec<String>>,
+}
+
+impl RecordingMessenger {
+    fn new() -> RecordingMessenger {
+        RecordingMessenger {
+            sent_messages: Mutex::new(vec![]),
+        }
+    }
+
+    fn sent(&self) -> Vec<String> {
+        self.sent_messages.lock().unwrap().clone()
+    }
+}
+
+impl Messenger for RecordingMessenger {
+    fn send(&self, message: &str) {
+        self.sent_messages
+            .lock()
+            .unwrap()
+            .push(String::from(message));
+    }
+}
+
+// Doesn't compile if the service stops being shareable between threads
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn it_tracks_tenants_separately() {
+    assert_send_sync::<QuotaService<RecordingMessenger>>();
+    assert_send_sync::<QuotaService<ChannelMessenger>>();
+
+    let service = QuotaService::with_thresholds(
+        RecordingMessenger::new(),
+        vec![
+            Threshold::new(50.0, "half way"),
+            Threshold::new(100.0, "full"),
+        ],
+    );
+    service.add_tenant("a", 10).unwrap();
+    service.add_tenant("b", 100).unwrap();
+
+    assert_eq!(service.record("a", 5), Ok(5));
+    assert_eq!(service.record("b", 5), Ok(5));
+    service.set_usage("b", 100).unwrap();
+    assert_eq!(service.messenger.sent(), ["[a] half way", "[b] full"]);
+
+    let snapshots = service.snapshots();
+    assert_eq!(snapshots.len(), 2);
+    assert_eq!(
+        snapshots[0],
+        UsageSnapshot {
+            tenant: String::from("a"),
+            used: 5,
+            max: 10,
+            threshold: Some(50.0),
+        }
+    );
+    assert_eq!(snapshots[1].to_string(), "b: 100/100 (100.0%)");
+
+    service.reset("b").unwrap();
+    assert_eq!(service.snapshot("b").unwrap().threshold, None);
+    service.record("b", 60).unwrap();
+    assert_eq!(service.messenger.sent().last().unwrap(), "[b] half way");
+    assert_eq!((service.records(), service.alerts_sent()), (3, 3));
+}
+
+fn it_rejects_unknown_and_duplicate_tenants() {
+    let service = QuotaService::new(RecordingMessenger::new());
+    service.add_tenant("a", 10).unwrap();
+
+    assert_eq!(
+        service.add_tenant("a", 20),
+        Err(QuotaError::TenantExists(String::from("a")))
+    );
+    assert_eq!(
+        service.record("missing", 1),
+        Err(QuotaError::UnknownTenant(String::from("missing")))
+    );
+    assert_eq!(
+        service.snapshot("missing").unwrap_err().to_string(),
+        "no tenant named missing"
+    );
+
+    service.remove_tenant("a").unwrap();
+    assert!(service.remove_tenant("a").is_err());
+    assert!(service.snapshots().is_empty());
+}
+
+fn it_survives_many_threads_hammering_it() {
+    const THREADS: usize = 8;
+    const TENANTS: usize = 10;
+    const RECORDS: usize = 1000;
+
+    let service = Arc::new(QuotaService::new(RecordingMessenger::new()));
+    for tenant in 0..TENANTS {
+        service
+            .add_tenant(&format!("tenant-{}", tenant), THREADS * RECORDS)
+            .unwrap();
+    }
+
+    // every thread records against every tenant, interleaved so threads collide on the same tenant
+    let workers: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let service = Arc::clone(&service);
+            thread::spawn(move || {
+                for i in 0..RECORDS * TENANTS {
+                    let tenant = format!("tenant-{}", i % TENANTS);
+                    service.record(&tenant, 1).unwrap();
+                }
+            })
+        })
+        .collect();
+    // snapshots taken while the workers run must never see a torn or decreasing value
+    let mut last_seen = [0; TENANTS];
+    while !workers.iter().all(|worker| worker.is_finished()) {
+        for (tenant, snapshot) in service.snapshots().iter().enumerate() {
+            assert!(snapshot.used >= last_seen[tenant]);
+            last_seen[tenant] = snapshot.used;
+        }
+        thread::yield_now();
+    }
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    assert!(service
+        .snapshots()
+        .iter()
+        .all(|snapshot| snapshot.used == THREADS * RECORDS));
+    assert_eq!(service.records(), (THREADS * TENANTS * RECORDS) as u64);
+    // usage only went up, so every tenant crossed 75%, 90% and 100% exactly once, in that order
+    assert_eq!(service.alerts_sent(), (TENANTS * 3) as u64);
+    let sent = service.messenger.sent();
+    for tenant in 0..TENANTS {
+        let prefix = format!("[tenant-{}] ", tenant);
+        let alerts: Vec<&str> = sent
+            .iter()
+            .filter_map(|message| message.strip_prefix(&prefix))
+            .collect();
+        let expected: Vec<String> = default_thresholds()
+            .into_iter()
+            .map(|threshold| threshold.message)
+            .collect();
+        assert_eq!(alerts, expected);
+    }
+}
+
+fn it_keeps_a_tenant_working_after_an_oversized_record() {
+    let service = QuotaService::new(RecordingMessenger::new());
+    service.add_tenant("a", 100).unwrap();
+    service.record("a", 10).unwrap();
+
+    let err = service.record("a", usize::MAX).unwrap_err();
+    assert_eq!(
+        err,
+        QuotaError::UsageOverflow {
+            tenant: String::from("a"),
+            amount: usize::MAX,
+        }
+    );
+    assert_eq!(
+        err.to_string(),
+        format!("recording {} would overflow the usage of a", usize::MAX)
+    );
+
+    // nothing was recorded and the tenant's lock isn't poisoned
+    assert_eq!(service.snapshot("a").unwrap().used, 10);
+    assert_eq!(service.record("a", 70), Ok(80));
+    assert_eq!(
+        service.messenger.sent(),
+        ["[a] Warning: You've used up over 75% of your quota!"]
+    );
+}
@@ -0,0 +1,551 @@
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::advance_rust::smart_pointers::{
+    default_thresholds, Messenger, MockMessenger, Threshold, ThresholdAlerts,
+};
+
+pub fn rate_limits() {
+    // Quotas over time
+
+    // LimitTracker compares one value against max forever. Real quotas are per period:
+    //  fixed window: usage counts from the start of the current period (every hour, every day)
+    //  and drops back to 0 when the next period starts
+    //  sliding window: usage is whatever was used in the last period, counted back from now,
+    //  so there is no moment where the whole quota becomes available at once
+    //  token bucket: a bucket holds up to capacity tokens and refills at a steady rate, every
+    //  request takes tokens out. Allows short bursts but limits the average rate.
+    // All three send the same threshold messages as LimitTracker when usage climbs.
+
+    // They ask a Clock for the time instead of calling Instant::now() themselves, so the tests
+    // can use a ManualClock and decide exactly how much time passes.
+
+    let clock = ManualClock::new();
+    let messenger = PrintMessenger;
+    let mut hourly = WindowedTracker::new(
+        &messenger,
+        &clock,
+        Window::Fixed(Duration::from_secs(3600)),
+        100,
+    )
+    .unwrap();
+    hourly.record(80).unwrap();
+    clock.advance(Duration::from_secs(3600));
+    println!("a new hour, usage back to {}", hourly.used());
+
+    let mut bucket = TokenBucket::new(&messenger, &clock, 10, 2.0).unwrap();
+    let allowed = (0..12).filter(|_| bucket.try_acquire(1)).count();
+    println!("burst of 12 requests, {} allowed", allowed);
+    clock.advance(Duration::from_secs(1));
+    println!("a second later {} tokens are back", bucket.available());
+
+    it_resets_fixed_windows_at_the_period_boundary();
+    it_expires_usage_from_a_sliding_window();
+    it_refills_the_token_bucket_over_time();
+    it_works_with_the_system_clock();
+    it_rejects_limits_that_cant_work();
+}
+
+struct PrintMessenger;
+
+impl Messenger for PrintMessenger {
+    fn send(&self, msg: &str) {
+        println!("{}", msg);
+    }
+}
+
+// The time since some fixed starting point, the only thing the limiters need from a clock
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+// Real time, counted from when the clock was created
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+// Time only moves when advance() is called
+pub struct ManualClock {
+    now: Cell<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        ManualClock {
+            now: Cell::new(Duration::ZERO),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateLimitError {
+    // a window of no time has no periods to count in
+    ZeroWindow,
+    ZeroCapacity,
+    // the refill rate has to be a finite number of tokens per second, 0 or more
+    InvalidRefillRate(f64),
+    // recording amount would take the usage past usize::MAX, nothing was recorded
+    UsageOverflow { amount: usize },
+}
+
+impl fmt::Display for RateLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateLimitError::ZeroWindow => write!(f, "the window can't be zero seconds long"),
+            RateLimitError::ZeroCapacity => write!(f, "the bucket needs a capacity of at least 1"),
+            RateLimitError::InvalidRefillRate(rate) => {
+                write!(f, "{} is not a valid refill rate", rate)
+            }
+            RateLimitError::UsageOverflow { amount } => {
+                write!(f, "recording {} would overflow the usage", amount)
+            }
+        }
+    }
+}
+
+impl Error for RateLimitError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Window {
+    Fixed(Duration),
+    Sliding(Duration),
+}
+
+pub struct WindowedTracker<'a, T: Messenger, C: Clock> {
+    messenger: &'a T,
+    clock: &'a C,
+    window: Window,
+    max: usize,
+    alerts: ThresholdAlerts,
+    // Fixed: the index of the current period, every record in it is added up in `used`
+    period: u128,
+    used: usize,
+    // Sliding: every record still inside the window, oldest first
+    records: VecDeque<(Duration, usize)>,
+}
+
+impl<'a, T, C> WindowedTracker<'a, T, C>
+where
+    T: Messenger,
+    C: Clock,
+{
+    pub fn new(
+        messenger: &'a T,
+        clock: &'a C,
+        window: Window,
+        max: usize,
+    ) -> Result<WindowedTracker<'a, T, C>, RateLimitError> {
+        WindowedTracker::with_thresholds(messenger, clock, window, max, default_thresholds())
+    }
+
+    pub fn with_thresholds(
+        messenger: &'a T,
+        clock: &'a C,
+        window: Window,
+        max: usize,
+        thresholds: Vec<Threshold>,
+    ) -> Result<WindowedTracker<'a, T, C>, RateLimitError> {
+        let (Window::Fixed(length) | Window::Sliding(length)) = window;
+        if length.is_zero() {
+            return Err(RateLimitError::ZeroWindow);
+        }
+        let mut tracker = WindowedTracker {
+            messenger,
+            clock,
+            window,
+            max,
+            alerts: ThresholdAlerts::new(thresholds),
+            period: 0,
+            used: 0,
+            records: VecDeque::new(),
+        };
+        tracker.period = tracker.current_period();
+        Ok(tracker)
+    }
+
+    fn current_period(&self) -> u128 {
+        match self.window {
+            Window::Fixed(length) => self.clock.now().as_nanos() / length.as_nanos(),
+            Window::Sliding(_) => 0,
+        }
+    }
+
+    // Forgets whatever fell out of the window since the last call
+    fn expire(&mut self) {
+        match self.window {
+            Window::Fixed(_) => {
+                let period = self.current_period();
+                if period != self.period {
+                    // a new period is a fresh quota, every threshold can notify again
+                    self.period = period;
+                    self.used = 0;
+                    self.alerts.reset();
+                }
+            }
+            Window::Sliding(length) => {
+                let now = self.clock.now();
+                while let Some(&(at, amount)) = self.records.front() {
+                    if now.saturating_sub(at) < length {
+                        break;
+                    }
+                    self.records.pop_front();
+                    self.used -= amount;
+                }
+            }
+        }
+    }
+
+    fn percentage_of_max(&self) -> f64 {
+        self.used as f64 / self.max as f64 * 100.0
+    }
+
+    // Adds amount to the usage of the current window, notifies if that crosses a threshold and
+    // returns the new usage. Not saturating: a sliding window subtracts every record again when it
+    // expires, so the usage has to be the exact sum of them.
+    pub fn record(&mut self, amount: usize) -> Result<usize, RateLimitError> {
+        self.expire();
+        let used = self
+            .used
+            .checked_add(amount)
+            .ok_or(RateLimitError::UsageOverflow { amount })?;
+        if let Window::Sliding(_) = self.window {
+            self.records.push_back((self.clock.now(), amount));
+        }
+        self.used = used;
+        let percentage_of_max = self.percentage_of_max();
+        self.alerts.notify(percentage_of_max, self.messenger);
+        Ok(used)
+    }
+
+    // The usage in the current window. Falling usage never sends anything, but it does re-arm
+    // thresholds once it is below their hysteresis band.
+    pub fn used(&mut self) -> usize {
+        self.expire();
+        let percentage_of_max = self.percentage_of_max();
+        self.alerts.update(percentage_of_max);
+        self.used
+    }
+
+    pub fn remaining(&mut self) -> usize {
+        self.max.saturating_sub(self.used())
+    }
+
+    pub fn current_threshold(&self) -> Option<&Threshold> {
+        self.alerts.current()
+    }
+}
+
+pub struct TokenBucket<'a, T: Messenger, C: Clock> {
+    messenger: &'a T,
+    clock: &'a C,
+    capacity: usize,
+    refill_per_second: f64,
+    // fractional, so a slow refill rate still adds up between calls
+    tokens: f64,
+    refilled_at: Duration,
+    // usage here is how empty the bucket is: 75% means only a quarter of the tokens are left
+    alerts: ThresholdAlerts,
+}
+
+impl<'a, T, C> TokenBucket<'a, T, C>
+where
+    T: Messenger,
+    C: Clock,
+{
+    // Starts full
+    pub fn new(
+        messenger: &'a T,
+        clock: &'a C,
+        capacity: usize,
+        refill_per_second: f64,
+    ) -> Result<TokenBucket<'a, T, C>, RateLimitError> {
+        TokenBucket::with_thresholds(
+            messenger,
+            clock,
+            capacity,
+            refill_per_second,
+            default_thresholds(),
+        )
+    }
+
+    pub fn with_thresholds(
+        messenger: &'a T,
+        clock: &'a C,
+        capacity: usize,
+        refill_per_second: f64,
+        thresholds: Vec<Threshold>,
+    ) -> Result<TokenBucket<'a, T, C>, RateLimitError> {
+        if capacity == 0 {
+            return Err(RateLimitError::ZeroCapacity);
+        }
+        // 0 is allowed, a bucket that never refills
+        if !refill_per_second.is_finite() || refill_per_second < 0.0 {
+            return Err(RateLimitError::InvalidRefillRate(refill_per_second));
+        }
+        Ok(TokenBucket {
+            messenger,
+            clock,
+            capacity,
+            refill_per_second,
+            tokens: capacity as f64,
+            refilled_at: clock.now(),
+            alerts: ThresholdAlerts::new(thresholds),
+        })
+    }
+
+    fn refill(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_second).min(self.capacity as f64);
+        self.refilled_at = now;
+    }
+
+    fn percentage_used(&self) -> f64 {
+        (self.capacity as f64 - self.tokens) / self.capacity as f64 * 100.0
+    }
+
+    // Takes n tokens if there are that many, otherwise takes nothing and returns false.
+    // A refused request counts as 100% used, so running dry always gets reported.
+    pub fn try_acquire(&mut self, n: usize) -> bool {
+        self.refill();
+        let acquired = self.tokens >= n as f64;
+        if acquired {
+            self.tokens -= n as f64;
+        }
+        let percentage_used = if acquired {
+            self.percentage_used()
+        } else {
+            100.0
+        };
+        self.alerts.notify(percentage_used, self.messenger);
+        acquired
+    }
+
+    // Whole tokens available right now
+    pub fn available(&mut self) -> usize {
+        self.refill();
+        let percentage_used = self.percentage_used();
+        self.alerts.update(percentage_used);
+        self.tokens.floor() as usize
+    }
+
+    // How long until n tokens are available, zero if they already are. None if that never happens:
+    // n is more than the bucket holds, the bucket doesn't refill, or the wait doesn't fit a Duration.
+    pub fn wait_time(&mut self, n: usize) -> Option<Duration> {
+        self.refill();
+        let missing = n as f64 - self.tokens;
+        if missing <= 0.0 {
+            Some(Duration::ZERO)
+        } else if n > self.capacity {
+            None
+        } else {
+            Duration::try_from_secs_f64(missing / self.refill_per_second).ok()
+        }
+    }
+}
+
+fn it_resets_fixed_windows_at_the_period_boundary() {
+    let clock = ManualClock::new();
+    let messenger = MockMessenger::new();
+    let mut tracker = WindowedTracker::with_thresholds(
+        &messenger,
+        &clock,
+        Window::Fixed(Duration::from_secs(60)),
+        10,
+        vec![Threshold::new(80.0, "80%")],
+    )
+    .unwrap();
+
+    assert_eq!(tracker.record(5), Ok(5));
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(tracker.record(3), Ok(8));
+    assert_eq!(tracker.used(), 8);
+    assert_eq!(*messenger.sent_messages.borrow(), ["80%"]);
+
+    // one second into the next minute everything from the previous one is gone
+    clock.advance(Duration::from_secs(31));
+    assert_eq!(tracker.used(), 0);
+    assert_eq!(tracker.remaining(), 10);
+    assert!(tracker.current_threshold().is_none());
+
+    // and the threshold notifies again in the new period
+    tracker.record(9).unwrap();
+    assert_eq!(*messenger.sent_messages.borrow(), ["80%", "80%"]);
+
+    // periods are aligned to the clock, not to the first record: 59 seconds after 1:01 is 2:00
+    clock.advance(Duration::from_secs(59));
+    tracker.record(1).unwrap();
+    assert_eq!(tracker.used(), 1);
+}
+
+fn it_expires_usage_from_a_sliding_window() {
+    let clock = ManualClock::new();
+    let messenger = MockMessenger::new();
+    let mut tracker = WindowedTracker::with_thresholds(
+        &messenger,
+        &clock,
+        Window::Sliding(Duration::from_secs(60)),
+        10,
+        vec![Threshold::new(80.0, "80%")],
+    )
+    .unwrap();
+
+    tracker.record(3).unwrap();
+    clock.advance(Duration::from_secs(20));
+    tracker.record(3).unwrap();
+    assert!(messenger.sent_messages.borrow().is_empty());
+
+    // 50 seconds later the first record is still inside the last minute
+    clock.advance(Duration::from_secs(30));
+    tracker.record(3).unwrap();
+    assert_eq!(*messenger.sent_messages.borrow(), ["80%"]);
+    assert_eq!(tracker.used(), 9);
+
+    // 60 seconds after it, the first record drops out but the rest stays
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(tracker.used(), 6);
+    assert!(tracker.current_threshold().is_none());
+
+    // a minute after the last record nothing is left
+    clock.advance(Duration::from_secs(50));
+    assert_eq!(tracker.used(), 0);
+    tracker.record(8).unwrap();
+    assert_eq!(messenger.sent_messages.borrow().len(), 2);
+}
+
+fn it_refills_the_token_bucket_over_time() {
+    let clock = ManualClock::new();
+    let messenger = MockMessenger::new();
+    let mut bucket = TokenBucket::with_thresholds(
+        &messenger,
+        &clock,
+        4,
+        0.5,
+        vec![
+            Threshold::new(75.0, "running low"),
+            Threshold::new(100.0, "empty"),
+        ],
+    )
+    .unwrap();
+
+    // a full bucket allows a burst of capacity requests
+    assert!(bucket.try_acquire(2));
+    assert!(messenger.sent_messages.borrow().is_empty());
+    assert!(bucket.try_acquire(1));
+    assert!(bucket.try_acquire(1));
+    assert!(!bucket.try_acquire(1));
+    assert_eq!(*messenger.sent_messages.borrow(), ["running low", "empty"]);
+    assert_eq!(bucket.wait_time(1), Some(Duration::from_secs(2)));
+
+    // half a token per second
+    clock.advance(Duration::from_secs(3));
+    assert_eq!(bucket.available(), 1);
+    // 1.5 of 4 tokens back is below both hysteresis bands, so draining it again notifies again
+    assert!(bucket.try_acquire(1));
+    assert!(!bucket.try_acquire(1));
+    assert_eq!(bucket.wait_time(1), Some(Duration::from_secs(1)));
+    assert_eq!(
+        *messenger.sent_messages.borrow(),
+        ["running low", "empty", "running low", "empty"]
+    );
+
+    // never more than capacity, however long it waits
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(bucket.available(), 4);
+    assert_eq!(bucket.wait_time(4), Some(Duration::ZERO));
+    // asking for more than the bucket can ever hold is refused and reported, and can't be waited for
+    assert!(!bucket.try_acquire(5));
+    assert_eq!(bucket.wait_time(5), None);
+    assert_eq!(messenger.sent_messages.borrow().len(), 5);
+}
+
+fn it_works_with_the_system_clock() {
+    let clock = SystemClock::new();
+    let messenger = MockMessenger::new();
+    let mut bucket = TokenBucket::new(&messenger, &clock, 1, 1000.0).unwrap();
+    assert!(bucket.try_acquire(1));
+    // a millisecond refills a token at this rate
+    std::thread::sleep(Duration::from_millis(5));
+    assert!(bucket.try_acquire(1));
+    assert!(clock.now() >= Duration::from_millis(5));
+}
+
+fn it_rejects_limits_that_cant_work() {
+    let clock = ManualClock::new();
+    let messenger = MockMessenger::new();
+    for window in [
+        Window::Fixed(Duration::ZERO),
+        Window::Sliding(Duration::ZERO),
+    ] {
+        assert!(matches!(
+            WindowedTracker::new(&messenger, &clock, window, 10),
+            Err(RateLimitError::ZeroWindow)
+        ));
+    }
+    assert!(matches!(
+        TokenBucket::new(&messenger, &clock, 0, 1.0),
+        Err(RateLimitError::ZeroCapacity)
+    ));
+    for rate in [-1.0, f64::NAN, f64::INFINITY] {
+        let err = TokenBucket::new(&messenger, &clock, 1, rate).err().unwrap();
+        assert!(matches!(err, RateLimitError::InvalidRefillRate(_)));
+    }
+    assert_eq!(
+        RateLimitError::InvalidRefillRate(-1.0).to_string(),
+        "-1 is not a valid refill rate"
+    );
+
+    // a bucket that never refills can be emptied, but not waited for
+    let mut bucket = TokenBucket::new(&messenger, &clock, 2, 0.0).unwrap();
+    assert!(bucket.try_acquire(2));
+    assert_eq!(bucket.wait_time(1), None);
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(bucket.available(), 0);
+    // and one that refills too slowly for a Duration to hold the wait
+    let mut bucket = TokenBucket::new(&messenger, &clock, 1, f64::MIN_POSITIVE).unwrap();
+    assert!(bucket.try_acquire(1));
+    assert_eq!(bucket.wait_time(1), None);
+
+    // an oversized record is refused and leaves the usage as it was, on both kinds of window
+    for window in [
+        Window::Fixed(Duration::from_secs(60)),
+        Window::Sliding(Duration::from_secs(60)),
+    ] {
+        let mut tracker = WindowedTracker::new(&messenger, &clock, window, 10).unwrap();
+        tracker.record(5).unwrap();
+        assert_eq!(
+            tracker.record(usize::MAX),
+            Err(RateLimitError::UsageOverflow { amount: usize::MAX })
+        );
+        assert_eq!(tracker.used(), 5);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(tracker.used(), 0);
+    }
+}
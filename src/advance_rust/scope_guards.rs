@@ -0,0 +1,312 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::test_support::catch_quiet_panic;
+
+pub fn scope_guards() {
+    // Scope Guards
+
+    // CustomPointer's Drop runs when the value goes out of scope, whichever way we leave the scope:
+    // falling off the end, return, ?, break, or a panic unwinding through it. That makes Drop the
+    // place for cleanup that must always happen, and a value whose only job is to run that cleanup
+    // is called a guard (RAII: the resource is released when the guard is dropped).
+
+    // defer runs a closure at the end of the scope, dismiss() cancels it
+    {
+        let _cleanup = defer(|| println!("deferred: runs last"));
+        println!("deferred: body runs first");
+    }
+
+    // a temporary directory that is deleted with everything in it when the guard goes away
+    let dir = TempDir::new("scope-guards").unwrap();
+    fs::write(dir.path().join("notes.txt"), "temporary").unwrap();
+    println!(
+        "temp dir {} exists: {}",
+        dir.path().display(),
+        dir.path().exists()
+    );
+    let path = dir.path().to_path_buf();
+    drop(dir);
+    println!("after drop it exists: {}", path.exists());
+
+    // a value that is put back when the scope ends
+    let mut verbose = false;
+    {
+        let verbose = Restore::replace(&mut verbose, true);
+        println!("verbose inside the scope: {}", *verbose);
+    }
+    println!("verbose after the scope: {}", verbose);
+
+    it_runs_deferred_closures_unless_dismissed();
+    it_deletes_temp_dirs_even_when_panicking();
+    it_releases_file_locks_on_drop();
+    it_restores_values_on_scope_exit();
+}
+
+// Runs the closure when dropped, unless dismiss() was called first
+pub struct Defer<F: FnOnce()> {
+    // None once dismissed
+    f: Option<F>,
+}
+
+pub fn defer<F: FnOnce()>(f: F) -> Defer<F> {
+    Defer { f: Some(f) }
+}
+
+impl<F: FnOnce()> Defer<F> {
+    pub fn dismiss(mut self) {
+        self.f = None;
+    }
+}
+
+impl<F: FnOnce()> Drop for Defer<F> {
+    fn drop(&mut self) {
+        if let Some(f) = self.f.take() {
+            f();
+        }
+    }
+}
+
+static NEXT_TEMP_DIR: AtomicU64 = AtomicU64::new(0);
+
+// A fresh directory under std::env::temp_dir(), removed recursively on drop
+pub struct TempDir {
+    // None once keep() took it
+    path: Option<PathBuf>,
+}
+
+impl TempDir {
+    pub fn new(prefix: &str) -> io::Result<TempDir> {
+        // process id + counter is unique within this machine, the time guards against a stale
+        // directory left behind by an earlier process with the same id
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or(0);
+        let path = std::env::temp_dir().join(format!(
+            "{}-{}-{}-{}",
+            prefix,
+            std::process::id(),
+            NEXT_TEMP_DIR.fetch_add(1, Ordering::Relaxed),
+            nanos
+        ));
+        // create_dir (not create_dir_all) fails if the directory is already there
+        fs::create_dir(&path)?;
+        Ok(TempDir { path: Some(path) })
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_deref().unwrap()
+    }
+
+    // Gives up ownership of the directory, it is not deleted
+    pub fn keep(mut self) -> PathBuf {
+        self.path.take().unwrap()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            // nothing useful to do with an error in drop, the directory is only left behind
+            let _ = fs::remove_dir_all(path);
+        }
+    }
+}
+
+// An exclusive advisory lock on a file, released when the guard is dropped.
+// Other processes (or other FileLocks in this one) that try to lock the same file wait or fail.
+pub struct FileLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl FileLock {
+    // Waits until the lock is free
+    pub fn lock(path: &Path) -> io::Result<FileLock> {
+        let file = FileLock::open(path)?;
+        file.lock()?;
+        Ok(FileLock {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    // Ok(None) if someone else holds the lock
+    pub fn try_lock(path: &Path) -> io::Result<Option<FileLock>> {
+        let file = FileLock::open(path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(FileLock {
+                file,
+                path: path.to_path_buf(),
+            })),
+            Err(fs::TryLockError::WouldBlock) => Ok(None),
+            Err(fs::TryLockError::Error(err)) => Err(err),
+        }
+    }
+
+    fn open(path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        // closing the file releases the lock too, unlocking first just doesn't rely on that
+        let _ = self.file.unlock();
+    }
+}
+
+// Replaces a value for the lifetime of the guard and puts the old one back when it is dropped.
+// The guard holds the &mut, so the new value is reached through the guard.
+pub struct Restore<'a, T> {
+    slot: &'a mut T,
+    saved: Option<T>,
+}
+
+impl<'a, T> Restore<'a, T> {
+    pub fn replace(slot: &'a mut T, value: T) -> Restore<'a, T> {
+        let saved = mem::replace(slot, value);
+        Restore {
+            slot,
+            saved: Some(saved),
+        }
+    }
+
+    // Remembers the current value and lets the guard change it in place
+    pub fn save(slot: &'a mut T) -> Restore<'a, T>
+    where
+        T: Clone,
+    {
+        let saved = slot.clone();
+        Restore {
+            slot,
+            saved: Some(saved),
+        }
+    }
+}
+
+impl<T> Deref for Restore<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.slot
+    }
+}
+
+impl<T> DerefMut for Restore<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.slot
+    }
+}
+
+impl<T> Drop for Restore<'_, T> {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.take() {
+            *self.slot = saved;
+        }
+    }
+}
+
+fn it_runs_deferred_closures_unless_dismissed() {
+    let mut log = Vec::new();
+    {
+        let _first = defer(|| log.push("first"));
+    }
+    {
+        let dismissed = defer(|| log.push("dismissed"));
+        dismissed.dismiss();
+    }
+    assert_eq!(log, ["first"]);
+
+    // guards run in reverse order, like any other locals, and also while unwinding
+    let log = std::cell::RefCell::new(Vec::new());
+    let message = catch_quiet_panic(|| {
+        let _outer = defer(|| log.borrow_mut().push("outer"));
+        let _inner = defer(|| log.borrow_mut().push("inner"));
+        panic!("boom");
+    });
+    assert_eq!(message.as_deref(), Some("boom"));
+    assert_eq!(*log.borrow(), ["inner", "outer"]);
+}
+
+fn it_deletes_temp_dirs_even_when_panicking() {
+    let first = TempDir::new("scope-guards-test").unwrap();
+    let second = TempDir::new("scope-guards-test").unwrap();
+    assert_ne!(first.path(), second.path());
+
+    fs::create_dir(first.path().join("nested")).unwrap();
+    fs::write(first.path().join("nested/file.txt"), "data").unwrap();
+    let first_path = first.path().to_path_buf();
+    drop(first);
+    assert!(!first_path.exists());
+
+    let kept = second.keep();
+    assert!(kept.exists());
+    fs::remove_dir(&kept).unwrap();
+
+    let mut panicked_path = None;
+    let message = catch_quiet_panic(|| {
+        let dir = TempDir::new("scope-guards-test").unwrap();
+        panicked_path = Some(dir.path().to_path_buf());
+        fs::write(dir.path().join("half-written"), "...").unwrap();
+        panic!("failed half way");
+    });
+    assert!(message.is_some());
+    assert!(!panicked_path.unwrap().exists());
+}
+
+fn it_releases_file_locks_on_drop() {
+    let dir = TempDir::new("scope-guards-lock").unwrap();
+    let path = dir.path().join("users.lock");
+
+    let lock = FileLock::lock(&path).unwrap();
+    assert_eq!(lock.path(), path);
+    assert!(FileLock::try_lock(&path).unwrap().is_none());
+    drop(lock);
+
+    let lock = FileLock::try_lock(&path).unwrap();
+    assert!(lock.is_some());
+    drop(lock);
+
+    // a panic while holding the lock still releases it
+    let message = catch_quiet_panic(|| {
+        let _lock = FileLock::lock(&path).unwrap();
+        panic!("crashed while holding the lock");
+    });
+    assert!(message.is_some());
+    assert!(FileLock::try_lock(&path).unwrap().is_some());
+}
+
+fn it_restores_values_on_scope_exit() {
+    let mut depth = 1;
+    {
+        let mut guard = Restore::replace(&mut depth, 5);
+        assert_eq!(*guard, 5);
+        *guard += 1;
+        assert_eq!(*guard, 6);
+    }
+    assert_eq!(depth, 1);
+
+    let mut names = vec![String::from("kartik")];
+    let message = catch_quiet_panic(|| {
+        let mut names = Restore::save(&mut names);
+        names.push(String::from("half added"));
+        panic!("validation failed");
+    });
+    assert!(message.is_some());
+    assert_eq!(names, ["kartik"]);
+}
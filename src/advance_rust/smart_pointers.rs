@@ -2,7 +2,13 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 
-use std::ops::Deref;
+use std::alloc::{self, Layout};
+use std::borrow::{Borrow, BorrowMut};
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
 use std::rc::Rc;
 
 pub fn smart_pointers() {
@@ -153,6 +159,32 @@ pub fn smart_pointers() {
 
     // Implicit Deref Coercions with Functions and Methods
 
+    // Deref coercion converts a reference to a type that implements Deref into a reference to another type.
+    // hello takes a &str, but we can pass it a &MyBox<String>: MyBox<String> derefs to String,
+    // and String derefs to str. The compiler calls deref as many times as needed, at compile time,
+    // so there is no runtime cost.
+
+    let m = MyBox::new(String::from("Rust"));
+    hello(&m);
+
+    // without deref coercion we would have to write it out ourselves
+    hello(&(*m)[..]);
+
+    // Rust does deref coercion in three cases:
+    // From &T to &U when T: Deref<Target=U>
+    // From &mut T to &mut U when T: DerefMut<Target=U>
+    // From &mut T to &U when T: Deref<Target=U>
+    // but never from &T to &mut U, an immutable reference can't become a mutable one.
+
+    let mut m = MyBox::new(String::from("Rust"));
+    shout(&mut m);
+    hello(&m);
+    *m = String::from("Ferris");
+    println!("MyBox prints like what it holds: {} {:?}", m, m);
+
+    it_coerces_through_several_derefs();
+    it_owns_and_frees_its_value();
+
     // Running Code on Cleanup with the Drop Trait
 
     // The second trait important to the smart pointer pattern is Drop,
@@ -234,6 +266,16 @@ pub fn smart_pointers() {
 
     // reference count: the implementation of the Drop trait decreases the reference count automatically when an Rc<T> value goes out of scope.
 
+    // The List above is generic and has a small persistent list API: push_front returns a new list
+    // whose tail is the old one, so b and c from the example become a.push_front(3) and a.push_front(4).
+    let a: Rc<List<i32>> = List::new().push_front(10).push_front(5);
+    let b = a.push_front(3);
+    println!("a = {a}, b = {b}, count of a = {}", Rc::strong_count(&a));
+
+    it_shares_tails_between_lists();
+    it_collects_and_iterates();
+    it_drops_a_long_list_without_overflowing_the_stack();
+
     // RefCell<T> and the Interior Mutability Pattern
     
     // Interior mutability is a design pattern in Rust that allows us to mutate data even
@@ -280,6 +322,16 @@ pub fn smart_pointers() {
     // https://doc.rust-lang.org/book/ch15-05-interior-mutability.html#:~:text=Listing%2015%2D20%3A%20A%20library%20to%20keep%20track%20of%20how%20close%20a%20value%20is%20to%20a%20maximum%20value%20and%20warn%20when%20the%20value%20is%20at%20certain%20levels
 
     it_sends_an_over_75_percent_warning_message();
+    it_sends_an_over_90_percent_warning_message();
+    it_sends_an_over_quota_error_message();
+
+    // LimitTracker takes its thresholds and messages from the caller and only sends a message when
+    // the value crosses a threshold on the way up. On the way down it has to fall a few percent
+    // below the threshold (the hysteresis) before the next crossing sends again, so a value hovering
+    // around a threshold doesn't send the same warning over and over.
+    it_only_notifies_when_crossing_a_threshold();
+    it_rearms_after_falling_below_the_hysteresis_band();
+    it_uses_custom_thresholds_and_resets();
 
     // we can't do this self.sent_messages.push(String::from(message));
     // as we take a immutable reference of the message
@@ -302,13 +354,15 @@ pub fn smart_pointers() {
 
 use std::cell::RefCell;
 
-struct MockMessenger {
+use crate::advance_rust::mock::{contains, eq, Mock, MockMethod, Times};
+
+pub(crate) struct MockMessenger {
     // sent_messages: Vec<String>,
-    sent_messages: RefCell<Vec<String>>,
+    pub(crate) sent_messages: RefCell<Vec<String>>,
 }
 
 impl MockMessenger {
-    fn new() -> MockMessenger {
+    pub(crate) fn new() -> MockMessenger {
         MockMessenger {
             // sent_messages: vec![],
             sent_messages: RefCell::new(vec![]),
@@ -323,24 +377,176 @@ impl Messenger for MockMessenger {
     }
 }
 
+// The same test double built with the mocking toolkit, it checks what was sent and how often
+struct MessengerMock {
+    send: MockMethod<String, ()>,
+}
+
+impl MessengerMock {
+    fn new(mock: &Mock) -> MessengerMock {
+        MessengerMock {
+            send: mock.method("send"),
+        }
+    }
+}
+
+impl Messenger for MessengerMock {
+    fn send(&self, msg: &str) {
+        self.send.call(String::from(msg))
+    }
+}
+
 fn it_sends_an_over_75_percent_warning_message() {
-    let mut mock_messenger = MockMessenger::new();
-    let mut limit_tracker = LimitTracker::new(&mut mock_messenger, 100);
+    let mock = Mock::new();
+    let mock_messenger = MessengerMock::new(&mock);
+    mock_messenger.send.expect(eq(String::from(
+        "Warning: You've used up over 75% of your quota!",
+    )));
+    let mut limit_tracker = LimitTracker::new(&mock_messenger, 100);
+
+    limit_tracker.set_value(80);
+
+    mock_messenger.send.assert_verified();
+}
+
+fn it_sends_an_over_90_percent_warning_message() {
+    let mock = Mock::new();
+    let mock_messenger = MessengerMock::new(&mock);
+    let mut limit_tracker = LimitTracker::new(&mock_messenger, 100);
 
     limit_tracker.set_value(80);
+    limit_tracker.set_value(95);
+
+    mock.assert_calls(&[
+        r#"send("Warning: You've used up over 75% of your quota!")"#,
+        r#"send("Urgent warning: You've used up over 90% of your quota!")"#,
+    ]);
+}
 
-    // assert_eq!(mock_messenger.sent_messages.len(), 1);
-    assert_eq!(mock_messenger.sent_messages.borrow().len(), 1);
+fn it_sends_an_over_quota_error_message() {
+    let mock = Mock::new();
+    let mock_messenger = MessengerMock::new(&mock);
+    // jumping straight over the quota only sends the error, and only once
+    mock_messenger
+        .send
+        .expect(contains("over your quota"))
+        .times(Times::Exactly(1));
+    let mut limit_tracker = LimitTracker::new(&mock_messenger, 100);
+
+    limit_tracker.set_value(100);
+    limit_tracker.set_value(120);
+
+    mock_messenger.send.assert_verified();
+    assert_eq!(mock_messenger.send.call_count(), 1);
 }
 
 pub trait Messenger {
     fn send(&self, msg: &str);
 }
 
+// A level of usage, in percent of max, and the message sent when usage climbs past it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threshold {
+    pub percent: f64,
+    pub message: String,
+}
+
+impl Threshold {
+    pub fn new(percent: f64, message: &str) -> Threshold {
+        Threshold {
+            percent,
+            message: String::from(message),
+        }
+    }
+}
+
+// How far (in percentage points) usage has to fall below a threshold before crossing it again
+// sends another message. Without it a value going 89, 90, 89, 90 would send a warning every other call.
+pub const DEFAULT_HYSTERESIS: f64 = 5.0;
+
+// The usual 75%, 90% and 100% warnings
+pub fn default_thresholds() -> Vec<Threshold> {
+    vec![
+        Threshold::new(75.0, "Warning: You've used up over 75% of your quota!"),
+        Threshold::new(
+            90.0,
+            "Urgent warning: You've used up over 90% of your quota!",
+        ),
+        Threshold::new(100.0, "Error: You are over your quota!"),
+    ]
+}
+
+// Which thresholds a usage has crossed and which of those we have already sent a message for.
+// Shared by LimitTracker and the quota service, the messenger is up to them.
+#[derive(Debug, Clone)]
+pub struct ThresholdAlerts {
+    // sorted by percent, lowest first
+    thresholds: Vec<Threshold>,
+    hysteresis: f64,
+    // index of the highest threshold we have notified about and not yet fallen back below
+    level: Option<usize>,
+}
+
+impl ThresholdAlerts {
+    pub fn new(mut thresholds: Vec<Threshold>) -> ThresholdAlerts {
+        thresholds.sort_by(|a, b| a.percent.total_cmp(&b.percent));
+        ThresholdAlerts {
+            thresholds,
+            hysteresis: DEFAULT_HYSTERESIS,
+            level: None,
+        }
+    }
+
+    pub fn set_hysteresis(&mut self, percent: f64) {
+        self.hysteresis = percent;
+    }
+
+    // The highest threshold currently crossed, as far as notifications are concerned
+    pub fn current(&self) -> Option<&Threshold> {
+        self.level.map(|level| &self.thresholds[level])
+    }
+
+    // Moves to the new usage and returns the threshold to notify about, if usage climbed past one
+    // it wasn't already past. If it jumps past several at once only the highest one is returned.
+    pub fn update(&mut self, percentage_of_max: f64) -> Option<&Threshold> {
+        // on the way down a threshold is only left once we are hysteresis below it
+        while let Some(level) = self.level {
+            if percentage_of_max >= self.thresholds[level].percent - self.hysteresis {
+                break;
+            }
+            self.level = level.checked_sub(1);
+        }
+
+        let crossed = self
+            .thresholds
+            .iter()
+            .rposition(|threshold| percentage_of_max >= threshold.percent);
+        if crossed > self.level {
+            self.level = crossed;
+            crossed.map(|level| &self.thresholds[level])
+        } else {
+            None
+        }
+    }
+
+    // update(), sending the threshold's message if there is one
+    pub fn notify<T: Messenger>(&mut self, percentage_of_max: f64, messenger: &T) {
+        if let Some(threshold) = self.update(percentage_of_max) {
+            messenger.send(&threshold.message);
+        }
+    }
+
+    // Forgets every threshold crossed, the next crossing notifies again
+    pub fn reset(&mut self) {
+        self.level = None;
+    }
+}
+
 pub struct LimitTracker<'a, T: Messenger> {
     messenger: &'a T,
     value: usize,
     max: usize,
+    alerts: ThresholdAlerts,
 }
 
 impl<'a, T> LimitTracker<'a, T>
@@ -348,36 +554,326 @@ where
     T: Messenger,
 {
     pub fn new(messenger: &'a T, max: usize) -> LimitTracker<'a, T> {
+        LimitTracker::with_thresholds(messenger, max, default_thresholds())
+    }
+
+    pub fn with_thresholds(
+        messenger: &'a T,
+        max: usize,
+        thresholds: Vec<Threshold>,
+    ) -> LimitTracker<'a, T> {
         LimitTracker {
             messenger,
             value: 0,
             max,
+            alerts: ThresholdAlerts::new(thresholds),
         }
     }
 
+    pub fn hysteresis(mut self, percent: f64) -> LimitTracker<'a, T> {
+        self.alerts.set_hysteresis(percent);
+        self
+    }
+
+    pub fn value(&self) -> usize {
+        self.value
+    }
+
+    pub fn current_threshold(&self) -> Option<&Threshold> {
+        self.alerts.current()
+    }
+
+    // Sends a message only when the value climbs past a threshold it wasn't already past
     pub fn set_value(&mut self, value: usize) {
         self.value = value;
 
-        let percentage_of_max = self.value as f64 / self.max as f64;
+        let percentage_of_max = self.value as f64 / self.max as f64 * 100.0;
 
-        if percentage_of_max >= 1.0 {
-            self.messenger.send("Error: You are over your quota!");
-        } else if percentage_of_max >= 0.9 {
-            self.messenger
-                .send("Urgent warning: You've used up over 90% of your quota!");
-        } else if percentage_of_max >= 0.75 {
-            self.messenger
-                .send("Warning: You've used up over 75% of your quota!");
-        }
+        self.alerts.notify(percentage_of_max, self.messenger);
+    }
+
+    // Forgets the value and every threshold crossed, the next crossing notifies again
+    pub fn reset(&mut self) {
+        self.value = 0;
+        self.alerts.reset();
     }
 }
 
-enum List {
-    Cons(i32, Rc<List>),
+fn it_only_notifies_when_crossing_a_threshold() {
+    let messenger = MockMessenger::new();
+    let mut tracker = LimitTracker::new(&messenger, 100);
+
+    for value in [10, 80, 81, 85, 80, 89] {
+        tracker.set_value(value);
+    }
+    assert_eq!(
+        *messenger.sent_messages.borrow(),
+        ["Warning: You've used up over 75% of your quota!"]
+    );
+
+    // jumping over 90% and 100% in one go only sends the highest
+    tracker.set_value(150);
+    tracker.set_value(120);
+    assert_eq!(messenger.sent_messages.borrow().len(), 2);
+    assert_eq!(
+        messenger.sent_messages.borrow()[1],
+        "Error: You are over your quota!"
+    );
+    assert_eq!(tracker.current_threshold().unwrap().percent, 100.0);
+}
+
+fn it_rearms_after_falling_below_the_hysteresis_band() {
+    let messenger = MockMessenger::new();
+    let mut tracker = LimitTracker::new(&messenger, 200);
+
+    // 90% is 180, the band goes down to 85% = 170
+    tracker.set_value(180);
+    tracker.set_value(175);
+    tracker.set_value(181);
+    assert_eq!(messenger.sent_messages.borrow().len(), 1);
+
+    tracker.set_value(169);
+    assert_eq!(tracker.current_threshold().unwrap().percent, 75.0);
+    tracker.set_value(180);
+    assert_eq!(messenger.sent_messages.borrow().len(), 2);
+
+    // falling below every band re-arms all of them
+    tracker.set_value(0);
+    assert!(tracker.current_threshold().is_none());
+    tracker.set_value(150);
+    assert_eq!(
+        messenger.sent_messages.borrow()[2],
+        "Warning: You've used up over 75% of your quota!"
+    );
+
+    // without hysteresis the old behaviour of warning on every crossing comes back
+    let messenger = MockMessenger::new();
+    let mut tracker = LimitTracker::new(&messenger, 100).hysteresis(0.0);
+    for value in [90, 89, 90, 89, 90] {
+        tracker.set_value(value);
+    }
+    assert_eq!(messenger.sent_messages.borrow().len(), 3);
+}
+
+fn it_uses_custom_thresholds_and_resets() {
+    let messenger = MockMessenger::new();
+    let mut tracker = LimitTracker::with_thresholds(
+        &messenger,
+        1000,
+        vec![
+            Threshold::new(100.0, "quota used up"),
+            Threshold::new(50.0, "half way"),
+        ],
+    );
+
+    tracker.set_value(500);
+    tracker.set_value(999);
+    tracker.set_value(1000);
+    assert_eq!(
+        *messenger.sent_messages.borrow(),
+        ["half way", "quota used up"]
+    );
+
+    tracker.reset();
+    assert_eq!(tracker.value(), 0);
+    assert!(tracker.current_threshold().is_none());
+    tracker.set_value(1000);
+    assert_eq!(
+        *messenger.sent_messages.borrow(),
+        ["half way", "quota used up", "quota used up"]
+    );
+}
+
+// A persistent (immutable) list: pushing to the front creates a new head that shares the old list
+// as its tail, nothing is ever copied or mutated. Many lists can share the same tail through Rc.
+pub enum List<T> {
+    Cons(T, Rc<List<T>>),
     Nil,
 }
 use crate::advance_rust::smart_pointers::List::{Cons, Nil};
 
+impl<T> List<T> {
+    pub fn new() -> Rc<List<T>> {
+        Rc::new(Nil)
+    }
+
+    // self stays untouched and becomes the tail of the returned list
+    pub fn push_front(self: &Rc<Self>, value: T) -> Rc<List<T>> {
+        Rc::new(Cons(value, Rc::clone(self)))
+    }
+
+    pub fn head(&self) -> Option<&T> {
+        match self {
+            Cons(value, _) => Some(value),
+            Nil => None,
+        }
+    }
+
+    pub fn tail(&self) -> Option<&Rc<List<T>>> {
+        match self {
+            Cons(_, tail) => Some(tail),
+            Nil => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Nil)
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn iter(&self) -> ListIter<'_, T> {
+        ListIter { next: self }
+    }
+
+    // Moves the head and tail out of a Cons. List implements Drop, so `match self { Cons(v, t) => .. }`
+    // is not allowed to move out of it (E0509); ManuallyDrop + ptr::read does the same move by hand.
+    fn into_parts(self) -> Option<(T, Rc<List<T>>)> {
+        let list = ManuallyDrop::new(self);
+        match &*list {
+            Cons(value, tail) => unsafe { Some((ptr::read(value), ptr::read(tail))) },
+            Nil => None,
+        }
+    }
+}
+
+// The default drop is recursive: dropping a Cons drops its tail, which drops its tail, and so on.
+// A million element list means a million nested calls and a stack overflow. Instead we unlink the
+// nodes one at a time in a loop. A tail that is still shared (try_unwrap fails) belongs to someone
+// else as well, so we just give up our reference to it and stop.
+impl<T> Drop for List<T> {
+    fn drop(&mut self) {
+        let mut next = match mem::replace(self, Nil).into_parts() {
+            Some((_, tail)) => tail,
+            None => return,
+        };
+        while let Ok(list) = Rc::try_unwrap(next) {
+            match list.into_parts() {
+                Some((_, tail)) => next = tail,
+                None => break,
+            }
+        }
+    }
+}
+
+pub struct ListIter<'a, T> {
+    next: &'a List<T>,
+}
+
+impl<'a, T> Iterator for ListIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        match self.next {
+            Cons(value, tail) => {
+                self.next = tail;
+                Some(value)
+            }
+            Nil => None,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a List<T> {
+    type Item = &'a T;
+    type IntoIter = ListIter<'a, T>;
+
+    fn into_iter(self) -> ListIter<'a, T> {
+        self.iter()
+    }
+}
+
+// The first item of the iterator becomes the head: [5, 10] collects into (5 10)
+impl<T> FromIterator<T> for List<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> List<T> {
+        let items: Vec<T> = iter.into_iter().collect();
+        let mut items = items.into_iter().rev();
+        let mut list = match items.next() {
+            Some(last) => Cons(last, List::new()),
+            None => return Nil,
+        };
+        for item in items {
+            list = Cons(item, Rc::new(list));
+        }
+        list
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for (index, value) in self.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for List<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+fn it_shares_tails_between_lists() {
+    let a: Rc<List<i32>> = List::new().push_front(10).push_front(5);
+    let b = a.push_front(3);
+    let c = a.push_front(4);
+
+    assert_eq!(a.to_string(), "(5 10)");
+    assert_eq!(b.to_string(), "(3 5 10)");
+    assert_eq!(c.to_string(), "(4 5 10)");
+    // a, b's tail and c's tail are the same allocation
+    assert!(Rc::ptr_eq(b.tail().unwrap(), &a));
+    assert!(Rc::ptr_eq(c.tail().unwrap(), &a));
+    assert_eq!(Rc::strong_count(&a), 3);
+
+    assert_eq!(b.head(), Some(&3));
+    assert_eq!(b.len(), 3);
+    assert_eq!(b.tail().unwrap().tail().unwrap().head(), Some(&10));
+    assert_eq!(List::<i32>::new().head(), None);
+    assert!(List::<i32>::new().tail().is_none());
+
+    drop(b);
+    drop(c);
+    assert_eq!(Rc::strong_count(&a), 1);
+}
+
+fn it_collects_and_iterates() {
+    let list: List<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(list.to_string(), "(a b c)");
+    assert_eq!(format!("{:?}", list), "[\"a\", \"b\", \"c\"]");
+
+    let mut joined = String::new();
+    for value in &list {
+        joined.push_str(value);
+    }
+    assert_eq!(joined, "abc");
+
+    let empty: List<i32> = std::iter::empty().collect();
+    assert!(empty.is_empty());
+    assert_eq!(empty.to_string(), "()");
+}
+
+fn it_drops_a_long_list_without_overflowing_the_stack() {
+    let list: List<u32> = (0..1_000_000).collect();
+    assert_eq!(list.len(), 1_000_000);
+    drop(list);
+
+    // a shared tail survives when one of the lists pointing at it goes away
+    let shared: Rc<List<u32>> = Rc::new((0..1000).collect());
+    let first = shared.push_front(1);
+    let second = shared.push_front(2);
+    drop(shared);
+    drop(first);
+    assert_eq!(second.len(), 1001);
+}
+
 struct CustomPointer{
     data: String
 }
@@ -388,11 +884,47 @@ impl Drop for CustomPointer {
     }
 }
 
-struct MyBox<T>(T);
+// MyBox<T> owns a heap allocation of its own, like Box<T>, instead of just wrapping the value
+pub struct MyBox<T> {
+    ptr: NonNull<T>,
+    // tells the drop checker that dropping a MyBox<T> drops a T
+    marker: PhantomData<T>,
+}
+
+impl<T> MyBox<T> {
+    pub fn new(x: T) -> MyBox<T> {
+        let layout = Layout::new::<T>();
+        // zero sized types don't need memory, any aligned non null pointer will do
+        let ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            let raw = unsafe { alloc::alloc(layout) } as *mut T;
+            match NonNull::new(raw) {
+                Some(ptr) => ptr,
+                None => alloc::handle_alloc_error(layout),
+            }
+        };
+        // the memory is uninitialised, so write the value without dropping what is "there"
+        unsafe { ptr::write(ptr.as_ptr(), x) };
+        MyBox {
+            ptr,
+            marker: PhantomData,
+        }
+    }
 
-impl <T> MyBox<T> {
-    fn new(x: T) -> MyBox<T> {
-        MyBox(x)
+    // Moves the value back out of the heap and frees the allocation
+    pub fn into_inner(self) -> T {
+        let this = ManuallyDrop::new(self);
+        let value = unsafe { ptr::read(this.ptr.as_ptr()) };
+        unsafe { MyBox::free(this.ptr) };
+        value
+    }
+
+    unsafe fn free(ptr: NonNull<T>) {
+        let layout = Layout::new::<T>();
+        if layout.size() != 0 {
+            alloc::dealloc(ptr.as_ptr() as *mut u8, layout);
+        }
     }
 }
 
@@ -402,10 +934,129 @@ impl<T> Deref for MyBox<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+// DerefMut is what lets *y = ... work, and &mut MyBox<T> coerce to &mut T
+impl<T> DerefMut for MyBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for MyBox<T> {
+    fn drop(&mut self) {
+        // drop the value first, then give the memory back
+        unsafe {
+            ptr::drop_in_place(self.ptr.as_ptr());
+            MyBox::free(self.ptr);
+        }
+    }
+}
+
+impl<T> From<T> for MyBox<T> {
+    fn from(x: T) -> MyBox<T> {
+        MyBox::new(x)
     }
 }
 
+impl<T> AsRef<T> for MyBox<T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T> AsMut<T> for MyBox<T> {
+    fn as_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T> Borrow<T> for MyBox<T> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T> BorrowMut<T> for MyBox<T> {
+    fn borrow_mut(&mut self) -> &mut T {
+        self
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for MyBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+fn hello(name: &str) {
+    println!("Hello, {name}!");
+}
+
+fn shout(name: &mut String) {
+    name.make_ascii_uppercase();
+    name.push('!');
+}
+
+fn it_coerces_through_several_derefs() {
+    let mut m = MyBox::new(String::from("Rust"));
+
+    // &MyBox<String> -> &String -> &str, two deref calls inserted by the compiler
+    let name: &str = &m;
+    assert_eq!(name, "Rust");
+    // which is what we would have to write without deref coercion
+    let name: &str = &(*m)[..];
+    assert_eq!(name, "Rust");
+
+    // &mut MyBox<String> -> &mut String, and methods of String and str are found through Deref
+    shout(&mut m);
+    assert_eq!(m.len(), 5);
+    assert!(m.starts_with("RU"));
+    assert_eq!(m.to_string(), "RUST!");
+    assert_eq!(format!("{:?}", m), "\"RUST!\"");
+
+    // the coercion keeps going as long as there is a Deref to follow
+    let nested = MyBox::new(MyBox::new(String::from("deep")));
+    let name: &str = &nested;
+    assert_eq!(name, "deep");
+}
+
+fn it_owns_and_frees_its_value() {
+    let counter = Rc::new(());
+    let mut boxed = MyBox::from(Rc::clone(&counter));
+    assert_eq!(Rc::strong_count(&counter), 2);
+
+    *boxed = Rc::new(());
+    assert_eq!(Rc::strong_count(&counter), 1);
+    *boxed = Rc::clone(&counter);
+
+    let as_ref: &Rc<()> = boxed.as_ref();
+    let borrowed: &Rc<()> = boxed.borrow();
+    assert!(Rc::ptr_eq(as_ref, borrowed));
+    drop(boxed);
+    assert_eq!(Rc::strong_count(&counter), 1);
+
+    let boxed = MyBox::new(Rc::clone(&counter));
+    let inner = boxed.into_inner();
+    assert_eq!(Rc::strong_count(&counter), 2);
+    drop(inner);
+    assert_eq!(Rc::strong_count(&counter), 1);
+
+    // zero sized values never touch the allocator
+    let mut unit = MyBox::new(());
+    *unit.as_mut() = ();
+    *BorrowMut::<()>::borrow_mut(&mut unit) = ();
+    assert_eq!(*unit, ());
+}
+
 // enum List {
 //         Cons(i32, Box<List>),
 //         Nil,
@@ -0,0 +1,516 @@
+use std::cell::{Ref, RefCell, RefMut};
+use std::rc::{Rc, Weak};
+
+pub fn weak_pointers() {
+    // Preventing Reference Cycles: Turning an Rc<T> into a Weak<T>
+
+    // Rc::clone increases the strong_count and an Rc<T> is only cleaned up when its strong_count is 0.
+    // If two values point at each other with Rc, neither count can ever reach 0 and both are leaked.
+
+    // Rc::downgrade gives us a Weak<T> instead. It increases the weak_count, and the weak_count does not
+    // need to be 0 for the value to be cleaned up. A Weak<T> does not own the value, so before using it
+    // we call upgrade(), which returns Option<Rc<T>>: None if the value was already dropped.
+
+    // The rule of thumb: the owner points at what it owns with Rc, anything pointing back uses Weak.
+    // A doubly linked list owns its next node (Rc) and only refers to its previous node (Weak).
+    // A tree owns its children (Rc) and a child only refers to its parent (Weak).
+
+    let leaf = TreeNode::new(3);
+    println!(
+        "leaf strong = {}, weak = {}, parent = {:?}",
+        Rc::strong_count(&leaf),
+        Rc::weak_count(&leaf),
+        leaf.parent().map(|parent| parent.value)
+    );
+
+    {
+        let branch = TreeNode::new(5);
+        branch.add_child(Rc::clone(&leaf));
+
+        // branch owns leaf (leaf strong = 2), leaf only knows its parent weakly (branch weak = 1)
+        println!(
+            "branch strong = {}, weak = {}",
+            Rc::strong_count(&branch),
+            Rc::weak_count(&branch)
+        );
+        println!(
+            "leaf strong = {}, weak = {}, parent = {:?}",
+            Rc::strong_count(&leaf),
+            Rc::weak_count(&leaf),
+            leaf.parent().map(|parent| parent.value)
+        );
+    }
+
+    // branch went out of scope and was dropped, the Weak parent pointer of leaf now upgrades to None
+    println!(
+        "leaf strong = {}, weak = {}, parent = {:?}",
+        Rc::strong_count(&leaf),
+        Rc::weak_count(&leaf),
+        leaf.parent().map(|parent| parent.value)
+    );
+
+    it_pushes_and_pops_at_both_ends();
+    it_inserts_and_removes_through_a_cursor();
+    it_frees_every_list_node_on_drop();
+    it_links_tree_parents_weakly();
+    it_frees_every_tree_node_on_drop();
+}
+
+// Doubly linked list
+// next is a strong Rc, so the list owns its nodes from head to tail.
+// prev and the list's tail are Weak, so there is exactly one strong pointer to every node and
+// dropping the head drops the whole chain, no cycles.
+
+type Link<T> = Option<Rc<RefCell<Node<T>>>>;
+type WeakLink<T> = Option<Weak<RefCell<Node<T>>>>;
+
+struct Node<T> {
+    value: T,
+    next: Link<T>,
+    prev: WeakLink<T>,
+}
+
+impl<T> Node<T> {
+    fn new(value: T) -> Rc<RefCell<Node<T>>> {
+        Rc::new(RefCell::new(Node {
+            value,
+            next: None,
+            prev: None,
+        }))
+    }
+}
+
+pub struct DoublyLinkedList<T> {
+    head: Link<T>,
+    tail: WeakLink<T>,
+    len: usize,
+}
+
+impl<T> DoublyLinkedList<T> {
+    pub fn new() -> DoublyLinkedList<T> {
+        DoublyLinkedList {
+            head: None,
+            tail: None,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn tail_node(&self) -> Link<T> {
+        self.tail.as_ref().and_then(Weak::upgrade)
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let node = Node::new(value);
+        match self.head.take() {
+            Some(old_head) => {
+                old_head.borrow_mut().prev = Some(Rc::downgrade(&node));
+                node.borrow_mut().next = Some(old_head);
+            }
+            None => self.tail = Some(Rc::downgrade(&node)),
+        }
+        self.head = Some(node);
+        self.len += 1;
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        match self.tail_node() {
+            Some(old_tail) => self.insert_after(&old_tail, value),
+            None => self.push_front(value),
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let head = self.head.clone()?;
+        Some(self.unlink(head))
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let tail = self.tail_node()?;
+        Some(self.unlink(tail))
+    }
+
+    fn insert_after(&mut self, node: &Rc<RefCell<Node<T>>>, value: T) {
+        let new_node = Node::new(value);
+        let next = node.borrow_mut().next.take();
+        match &next {
+            Some(next) => next.borrow_mut().prev = Some(Rc::downgrade(&new_node)),
+            None => self.tail = Some(Rc::downgrade(&new_node)),
+        }
+        {
+            let mut new = new_node.borrow_mut();
+            new.prev = Some(Rc::downgrade(node));
+            new.next = next;
+        }
+        node.borrow_mut().next = Some(new_node);
+        self.len += 1;
+    }
+
+    fn insert_before(&mut self, node: &Rc<RefCell<Node<T>>>, value: T) {
+        let prev = node.borrow().prev.as_ref().and_then(Weak::upgrade);
+        match prev {
+            Some(prev) => self.insert_after(&prev, value),
+            None => self.push_front(value),
+        }
+    }
+
+    // Takes node out of the chain and returns its value. Once its neighbours stop pointing at it,
+    // the Rc we were handed is the only strong pointer left, so try_unwrap always succeeds.
+    fn unlink(&mut self, node: Rc<RefCell<Node<T>>>) -> T {
+        let (prev, next) = {
+            let mut node = node.borrow_mut();
+            (
+                node.prev.take().and_then(|prev| prev.upgrade()),
+                node.next.take(),
+            )
+        };
+
+        match &next {
+            Some(next) => next.borrow_mut().prev = prev.as_ref().map(Rc::downgrade),
+            None => self.tail = prev.as_ref().map(Rc::downgrade),
+        }
+        match &prev {
+            Some(prev) => prev.borrow_mut().next = next,
+            None => self.head = next,
+        }
+        self.len -= 1;
+
+        match Rc::try_unwrap(node) {
+            Ok(node) => node.into_inner().value,
+            Err(_) => panic!("list node is still shared after unlinking"),
+        }
+    }
+
+    // A cursor points at one node, or at the "ghost" position between the tail and the head
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.head.clone(),
+            list: self,
+        }
+    }
+
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            current: self.tail_node(),
+            list: self,
+        }
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut values = Vec::with_capacity(self.len);
+        let mut next = self.head.clone();
+        while let Some(node) = next {
+            values.push(node.borrow().value.clone());
+            next = node.borrow().next.clone();
+        }
+        values
+    }
+
+    // Walks the Weak prev pointers from the tail, handy to check both directions agree
+    pub fn to_vec_rev(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut values = Vec::with_capacity(self.len);
+        let mut prev = self.tail_node();
+        while let Some(node) = prev {
+            values.push(node.borrow().value.clone());
+            prev = node.borrow().prev.as_ref().and_then(Weak::upgrade);
+        }
+        values
+    }
+}
+
+impl<T> Default for DoublyLinkedList<T> {
+    fn default() -> DoublyLinkedList<T> {
+        DoublyLinkedList::new()
+    }
+}
+
+// Dropping head would drop next, which drops its next and so on, one nested call per node.
+// Popping in a loop keeps the stack flat however long the list is.
+impl<T> Drop for DoublyLinkedList<T> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+pub struct CursorMut<'a, T> {
+    list: &'a mut DoublyLinkedList<T>,
+    current: Link<T>,
+}
+
+// The cursor holds a strong Rc to its node. Without a Drop impl the borrow checker would let the list be
+// used again as soon as the cursor is no longer used, while the cursor (and its Rc) is only dropped at the
+// end of the scope, and unlink would then find the node still shared. Having a Drop impl makes the
+// cursor keep the list borrowed until it is actually gone.
+impl<T> Drop for CursorMut<'_, T> {
+    fn drop(&mut self) {}
+}
+
+impl<T> CursorMut<'_, T> {
+    pub fn move_next(&mut self) {
+        self.current = match &self.current {
+            Some(node) => node.borrow().next.clone(),
+            None => self.list.head.clone(),
+        };
+    }
+
+    pub fn move_prev(&mut self) {
+        self.current = match &self.current {
+            Some(node) => node.borrow().prev.as_ref().and_then(Weak::upgrade),
+            None => self.list.tail_node(),
+        };
+    }
+
+    pub fn current(&self) -> Option<Ref<'_, T>> {
+        self.current
+            .as_ref()
+            .map(|node| Ref::map(node.borrow(), |node| &node.value))
+    }
+
+    pub fn current_mut(&mut self) -> Option<RefMut<'_, T>> {
+        self.current
+            .as_ref()
+            .map(|node| RefMut::map(node.borrow_mut(), |node| &mut node.value))
+    }
+
+    // At the ghost position this inserts at the front of the list
+    pub fn insert_after(&mut self, value: T) {
+        match &self.current {
+            Some(node) => self.list.insert_after(node, value),
+            None => self.list.push_front(value),
+        }
+    }
+
+    // At the ghost position this inserts at the back of the list
+    pub fn insert_before(&mut self, value: T) {
+        match &self.current {
+            Some(node) => self.list.insert_before(node, value),
+            None => self.list.push_back(value),
+        }
+    }
+
+    // Removes the current node and moves the cursor to the node after it
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.current.take()?;
+        self.current = node.borrow().next.clone();
+        Some(self.list.unlink(node))
+    }
+}
+
+// Tree with Weak parent pointers, the book's Node example made generic.
+// children is a RefCell so a node that is already shared through Rc can still get new children,
+// parent is a RefCell so it can be set when the node is attached to a parent later on.
+pub struct TreeNode<T> {
+    pub value: T,
+    parent: RefCell<Weak<TreeNode<T>>>,
+    children: RefCell<Vec<Rc<TreeNode<T>>>>,
+}
+
+impl<T> TreeNode<T> {
+    pub fn new(value: T) -> Rc<TreeNode<T>> {
+        Rc::new(TreeNode {
+            value,
+            parent: RefCell::new(Weak::new()),
+            children: RefCell::new(vec![]),
+        })
+    }
+
+    pub fn add_child(self: &Rc<Self>, child: Rc<TreeNode<T>>) {
+        *child.parent.borrow_mut() = Rc::downgrade(self);
+        self.children.borrow_mut().push(child);
+    }
+
+    // Detaches the child at index, it keeps living as long as someone else holds an Rc to it
+    pub fn remove_child(&self, index: usize) -> Rc<TreeNode<T>> {
+        let child = self.children.borrow_mut().remove(index);
+        *child.parent.borrow_mut() = Weak::new();
+        child
+    }
+
+    pub fn parent(&self) -> Option<Rc<TreeNode<T>>> {
+        self.parent.borrow().upgrade()
+    }
+
+    pub fn children(&self) -> Vec<Rc<TreeNode<T>>> {
+        self.children.borrow().clone()
+    }
+
+    // Number of parent links between this node and the root
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut parent = self.parent();
+        while let Some(node) = parent {
+            depth += 1;
+            parent = node.parent();
+        }
+        depth
+    }
+
+    pub fn path_to_root(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        let mut path = vec![self.value.clone()];
+        let mut parent = self.parent();
+        while let Some(node) = parent {
+            path.push(node.value.clone());
+            parent = node.parent();
+        }
+        path
+    }
+}
+
+fn it_pushes_and_pops_at_both_ends() {
+    let mut list = DoublyLinkedList::new();
+    list.push_back(2);
+    list.push_back(3);
+    list.push_front(1);
+    assert_eq!(list.to_vec(), [1, 2, 3]);
+    assert_eq!(list.to_vec_rev(), [3, 2, 1]);
+    assert_eq!(list.len(), 3);
+
+    assert_eq!(list.pop_back(), Some(3));
+    assert_eq!(list.pop_front(), Some(1));
+    assert_eq!(list.pop_front(), Some(2));
+    assert_eq!(list.pop_back(), None);
+    assert!(list.is_empty());
+
+    list.push_back(4);
+    assert_eq!(list.to_vec_rev(), [4]);
+}
+
+fn it_inserts_and_removes_through_a_cursor() {
+    let mut list = DoublyLinkedList::new();
+    for value in [1, 2, 4, 5] {
+        list.push_back(value);
+    }
+
+    {
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_next();
+        assert_eq!(*cursor.current().unwrap(), 2);
+        cursor.insert_after(3);
+        cursor.insert_before(0);
+        *cursor.current_mut().unwrap() = 20;
+
+        // removing moves to the next node, 3 was inserted right after 20
+        assert_eq!(cursor.remove_current(), Some(20));
+        assert_eq!(*cursor.current().unwrap(), 3);
+
+        // walking off either end lands on the ghost position, insert_before there appends
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        assert!(cursor.current().is_none());
+        cursor.insert_before(6);
+        cursor.insert_after(-1);
+        cursor.move_prev();
+        assert_eq!(*cursor.current().unwrap(), 6);
+    }
+    assert_eq!(list.to_vec(), [-1, 1, 0, 3, 4, 5, 6]);
+    assert_eq!(list.to_vec_rev(), [6, 5, 4, 3, 0, 1, -1]);
+
+    let mut cursor = list.cursor_back_mut();
+    while cursor.current().is_some() {
+        cursor.remove_current();
+        cursor.move_prev();
+    }
+    drop(cursor);
+    assert!(list.is_empty());
+    assert_eq!(list.to_vec_rev(), Vec::<i32>::new());
+}
+
+fn it_frees_every_list_node_on_drop() {
+    let mut list = DoublyLinkedList::new();
+    for value in 0..5 {
+        list.push_back(value);
+    }
+
+    let mut nodes = vec![];
+    let mut next = list.head.clone();
+    while let Some(node) = next {
+        next = node.borrow().next.clone();
+        nodes.push(Rc::downgrade(&node));
+    }
+
+    // every node has one owner (head or the previous node's next) and two weak pointers:
+    // the one we hold here plus the next node's prev, or list.tail for the last node
+    for node in &nodes {
+        assert_eq!(node.strong_count(), 1);
+        assert_eq!(node.weak_count(), 2);
+    }
+
+    drop(list);
+    for node in &nodes {
+        assert_eq!(node.strong_count(), 0);
+        assert_eq!(node.weak_count(), 0);
+        assert!(node.upgrade().is_none());
+    }
+
+    // a long list is freed without a deep recursive drop
+    let mut long = DoublyLinkedList::new();
+    for value in 0..200_000 {
+        long.push_back(value);
+    }
+    drop(long);
+}
+
+fn it_links_tree_parents_weakly() {
+    let root = TreeNode::new("root");
+    let branch = TreeNode::new("branch");
+    let leaf = TreeNode::new("leaf");
+    root.add_child(Rc::clone(&branch));
+    branch.add_child(Rc::clone(&leaf));
+
+    assert_eq!(leaf.path_to_root(), ["leaf", "branch", "root"]);
+    assert_eq!(leaf.depth(), 2);
+    assert_eq!(root.children().len(), 1);
+    assert!(Rc::ptr_eq(&leaf.parent().unwrap(), &branch));
+
+    // parents are held weakly by their children, children strongly by their parents
+    assert_eq!(Rc::strong_count(&branch), 2);
+    assert_eq!(Rc::weak_count(&branch), 1);
+    assert_eq!(Rc::strong_count(&root), 1);
+    assert_eq!(Rc::weak_count(&root), 1);
+
+    let detached = branch.remove_child(0);
+    assert!(detached.parent().is_none());
+    assert_eq!(detached.depth(), 0);
+    assert_eq!(Rc::weak_count(&branch), 0);
+}
+
+fn it_frees_every_tree_node_on_drop() {
+    let root = TreeNode::new(0);
+    let mut weak_nodes = vec![Rc::downgrade(&root)];
+    for i in 1..4 {
+        let child = TreeNode::new(i);
+        for j in 0..3 {
+            let grandchild = TreeNode::new(i * 10 + j);
+            weak_nodes.push(Rc::downgrade(&grandchild));
+            child.add_child(grandchild);
+        }
+        weak_nodes.push(Rc::downgrade(&child));
+        root.add_child(child);
+    }
+
+    // only the tree itself owns its nodes now
+    assert!(weak_nodes.iter().all(|node| node.strong_count() == 1));
+
+    drop(root);
+    for node in &weak_nodes {
+        assert_eq!(node.strong_count(), 0);
+        assert_eq!(node.weak_count(), 0);
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::collections::my_hash_map::MyHashMap;
+
+// Benchmarks
+// Timings only mean something in an optimised build:
+// cargo run --release -- bench-map [keys]
+// Every workload runs a few rounds and the fastest round is reported, which filters out most of the
+// noise from other processes. black_box stops the optimiser from deleting work whose result is unused.
+
+const ROUNDS: usize = 5;
+
+// A xorshift generator, good enough to shuffle benchmark and test keys without pulling in a crate
+pub struct XorShift(pub u64);
+
+impl XorShift {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+// Fastest of ROUNDS runs of f
+pub fn measure<F: FnMut()>(mut f: F) -> Duration {
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        f();
+        best = best.min(start.elapsed());
+    }
+    best
+}
+
+pub fn report(workload: &str, baseline_name: &str, baseline: Duration, name: &str, ours: Duration) {
+    println!(
+        "{:<28} {:>10} {:>10.2?}   {:>10} {:>10.2?}   ({:.2}x)",
+        workload,
+        baseline_name,
+        baseline,
+        name,
+        ours,
+        ours.as_secs_f64() / baseline.as_secs_f64()
+    );
+}
+
+// The operations the workloads need, implemented for both maps so they run exactly the same code
+pub trait BenchMap<K, V> {
+    fn new_map() -> Self;
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+    fn get(&self, key: &K) -> Option<&V>;
+    fn remove(&mut self, key: &K) -> Option<V>;
+    fn clear(&mut self);
+}
+
+impl<K: Hash + Eq, V> BenchMap<K, V> for HashMap<K, V> {
+    fn new_map() -> HashMap<K, V> {
+        HashMap::new()
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        HashMap::insert(self, key, value)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        HashMap::get(self, key)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        HashMap::remove(self, key)
+    }
+
+    fn clear(&mut self) {
+        HashMap::clear(self)
+    }
+}
+
+impl<K: Hash + Eq, V> BenchMap<K, V> for MyHashMap<K, V> {
+    fn new_map() -> MyHashMap<K, V> {
+        MyHashMap::new()
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        MyHashMap::insert(self, key, value)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        MyHashMap::get(self, key)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        MyHashMap::remove(self, key)
+    }
+
+    fn clear(&mut self) {
+        MyHashMap::clear(self)
+    }
+}
+
+// The users example from main scaled up: name -> age inserts, lookups, updates, removes and a clear
+fn users_workload<M: BenchMap<String, i32>>(names: &[String]) {
+    let mut users = M::new_map();
+    for (age, name) in names.iter().enumerate() {
+        users.insert(name.clone(), age as i32);
+    }
+    for name in names {
+        black_box(users.get(name));
+    }
+    for name in names.iter().step_by(3) {
+        users.insert(name.clone(), 0);
+    }
+    for name in names.iter().step_by(2) {
+        black_box(users.remove(name));
+    }
+    users.clear();
+    black_box(&users);
+}
+
+fn insert_random<M: BenchMap<u64, u64>>(keys: &[u64]) -> M {
+    let mut map = M::new_map();
+    for key in keys {
+        map.insert(*key, *key);
+    }
+    map
+}
+
+fn lookup_random<M: BenchMap<u64, u64>>(map: &M, keys: &[u64]) {
+    for key in keys {
+        black_box(map.get(key));
+    }
+}
+
+fn remove_random<M: BenchMap<u64, u64>>(map: &mut M, keys: &[u64]) {
+    for key in keys {
+        black_box(map.remove(key));
+    }
+}
+
+fn compare<F, G>(workload: &str, std_run: F, my_run: G)
+where
+    F: FnMut(),
+    G: FnMut(),
+{
+    let baseline = measure(std_run);
+    let ours = measure(my_run);
+    report(workload, "HashMap", baseline, "MyHashMap", ours);
+}
+
+pub fn bench_hash_maps(size: usize) {
+    println!(
+        "MyHashMap vs std::collections::HashMap, {} keys, best of {} rounds",
+        size, ROUNDS
+    );
+
+    let names: Vec<String> = (0..size).map(|i| format!("user_{}", i)).collect();
+    compare(
+        "users workload",
+        || users_workload::<HashMap<String, i32>>(&names),
+        || users_workload::<MyHashMap<String, i32>>(&names),
+    );
+
+    let mut rng = XorShift(0x9E3779B97F4A7C15);
+    let keys: Vec<u64> = (0..size).map(|_| rng.next()).collect();
+    let misses: Vec<u64> = (0..size).map(|_| rng.next()).collect();
+
+    compare(
+        "random insert",
+        || drop(black_box(insert_random::<HashMap<u64, u64>>(&keys))),
+        || drop(black_box(insert_random::<MyHashMap<u64, u64>>(&keys))),
+    );
+
+    let std_map = insert_random::<HashMap<u64, u64>>(&keys);
+    let my_map = insert_random::<MyHashMap<u64, u64>>(&keys);
+    compare(
+        "random lookup (hits)",
+        || lookup_random(&std_map, &keys),
+        || lookup_random(&my_map, &keys),
+    );
+    compare(
+        "random lookup (misses)",
+        || lookup_random(&std_map, &misses),
+        || lookup_random(&my_map, &misses),
+    );
+
+    // removing needs a full map every round, so the rebuild is part of what is measured for both
+    compare(
+        "random insert + remove",
+        || remove_random(&mut insert_random::<HashMap<u64, u64>>(&keys), &keys),
+        || remove_random(&mut insert_random::<MyHashMap<u64, u64>>(&keys), &keys),
+    );
+}
+
+pub fn run_cli(args: &[String]) -> Result<(), String> {
+    let size = match args.first() {
+        Some(size) => size
+            .parse()
+            .map_err(|_| format!("number of keys must be a number, got {}", size))?,
+        None => 1_000_000,
+    };
+    bench_hash_maps(size);
+    Ok(())
+}
@@ -0,0 +1,786 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::{Bound, RangeBounds};
+
+use crate::collections::bench::XorShift;
+
+// Binary search trees
+// The Box section builds a recursive type, Cons(i32, Box<List>): every node owns the next one. A tree is
+// the same idea with two children, every node owns a left and a right subtree, Option<Box<Node>> each.
+// In a binary search tree every key in the left subtree is smaller than the node's key and every key in
+// the right subtree bigger, so a lookup goes left or right at every node, like a binary search.
+//
+// That is only fast while the tree is bushy. Inserting keys in order makes every node the right child of
+// the one before, a tree as tall as a list. Bst is the plain version, it walks the tree with loops (not
+// recursion) so even such a tree doesn't overflow the stack.
+// AvlTree keeps itself balanced: every node stores the height of its subtree, and when the heights of the
+// two sides of a node differ by more than one after an insert or remove, a rotation fixes it. Its height
+// stays below 1.44 log2(n), so every operation is O(log n).
+//
+//        d                b           rotating right: b moves up, d becomes its right child and
+//       / \              / \          b's old right subtree (c) becomes d's left subtree.
+//      b   e    ->      a   d         The in-order sequence a b c d e doesn't change.
+//     / \                  / \
+//    a   c                c   e
+
+pub fn bst() {
+    let mut plain = Bst::new();
+    let mut avl = AvlTree::new();
+    for key in 1..=1000 {
+        plain.insert(key, ());
+        avl.insert(key, ());
+    }
+    println!(
+        "1000 keys in order: Bst height {}, AvlTree height {}",
+        plain.height(),
+        avl.height()
+    );
+
+    it_supports_map_operations();
+    it_iterates_in_order_and_pre_order();
+    it_queries_ranges();
+    it_stays_balanced();
+    it_matches_btree_map_on_random_operations();
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    left: Link<K, V>,
+    right: Link<K, V>,
+    // of the subtree, a leaf has height 1. Only kept up to date by AvlTree.
+    height: usize,
+}
+
+type Link<K, V> = Option<Box<Node<K, V>>>;
+
+impl<K, V> Node<K, V> {
+    fn new(key: K, value: V) -> Box<Node<K, V>> {
+        Box::new(Node {
+            key,
+            value,
+            left: None,
+            right: None,
+            height: 1,
+        })
+    }
+}
+
+// The read only operations are the same for both trees
+
+fn get<'a, K, V, Q>(mut link: &'a Link<K, V>, key: &Q) -> Option<&'a V>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    while let Some(node) = link {
+        match key.cmp(node.key.borrow()) {
+            Ordering::Less => link = &node.left,
+            Ordering::Greater => link = &node.right,
+            Ordering::Equal => return Some(&node.value),
+        }
+    }
+    None
+}
+
+fn get_mut<'a, K, V, Q>(mut link: &'a mut Link<K, V>, key: &Q) -> Option<&'a mut V>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    while let Some(node) = link {
+        match key.cmp(node.key.borrow()) {
+            Ordering::Less => link = &mut node.left,
+            Ordering::Greater => link = &mut node.right,
+            Ordering::Equal => return Some(&mut node.value),
+        }
+    }
+    None
+}
+
+// Counted level by level, the plain tree doesn't store heights
+fn height<K, V>(root: &Link<K, V>) -> usize {
+    let mut level: Vec<&Node<K, V>> = root.iter().map(|node| &**node).collect();
+    let mut height = 0;
+    while !level.is_empty() {
+        height += 1;
+        level = level
+            .iter()
+            .flat_map(|node| node.left.iter().chain(node.right.iter()))
+            .map(|node| &**node)
+            .collect();
+    }
+    height
+}
+
+// The default drop would recurse once per level, as deep as a plain tree of sorted keys is tall
+fn drop_iteratively<K, V>(root: &mut Link<K, V>) {
+    let mut stack: Vec<Box<Node<K, V>>> = root.take().into_iter().collect();
+    while let Some(mut node) = stack.pop() {
+        stack.extend(node.left.take());
+        stack.extend(node.right.take());
+    }
+}
+
+fn range<K: Ord, V, R: RangeBounds<K>>(root: &Link<K, V>, range: R) -> Range<'_, K, V, R> {
+    // push the path to the first key in range, like Iter does with the path to the smallest key
+    let mut stack = Vec::new();
+    let mut link = root;
+    while let Some(node) = link {
+        let above_start = match range.start_bound() {
+            Bound::Included(start) => node.key >= *start,
+            Bound::Excluded(start) => node.key > *start,
+            Bound::Unbounded => true,
+        };
+        if above_start {
+            stack.push(&**node);
+            link = &node.left;
+        } else {
+            link = &node.right;
+        }
+    }
+    Range {
+        iter: Iter { stack },
+        range,
+    }
+}
+
+// In-order: smallest key first. The stack holds the nodes whose left subtree we are in.
+pub struct Iter<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iter<'a, K, V> {
+    fn new(root: &'a Link<K, V>) -> Iter<'a, K, V> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left_spine(root);
+        iter
+    }
+
+    fn push_left_spine(&mut self, mut link: &'a Link<K, V>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(&node.right);
+        Some((&node.key, &node.value))
+    }
+}
+
+// Pre-order: every node before its subtrees, left before right. Inserting the keys in this order
+// into an empty tree builds the same tree again.
+pub struct PreOrder<'a, K, V> {
+    stack: Vec<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for PreOrder<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.right.as_deref());
+        self.stack.extend(node.left.as_deref());
+        Some((&node.key, &node.value))
+    }
+}
+
+pub struct Range<'a, K, V, R> {
+    iter: Iter<'a, K, V>,
+    range: R,
+}
+
+impl<'a, K: Ord, V, R: RangeBounds<K>> Iterator for Range<'a, K, V, R> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let (key, value) = self.iter.next()?;
+        let below_end = match self.range.end_bound() {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        if below_end {
+            Some((key, value))
+        } else {
+            // keys only grow from here
+            self.iter.stack.clear();
+            None
+        }
+    }
+}
+
+pub struct Bst<K, V> {
+    root: Link<K, V>,
+    len: usize,
+}
+
+impl<K: Ord, V> Bst<K, V> {
+    pub fn new() -> Bst<K, V> {
+        Bst { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Returns the old value if the key was there already
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let mut link = &mut self.root;
+        // `while let Some(node) = link` would keep link borrowed until the end, and we still
+        // want to insert into it there, so every step looks at the node first and only then
+        // borrows it mutably to move on
+        while let Some(node) = link.as_ref() {
+            let ordering = key.cmp(&node.key);
+            let node = link.as_mut().unwrap();
+            match ordering {
+                Ordering::Less => link = &mut node.left,
+                Ordering::Greater => link = &mut node.right,
+                Ordering::Equal => return Some(std::mem::replace(&mut node.value, value)),
+            }
+        }
+        *link = Some(Node::new(key, value));
+        self.len += 1;
+        None
+    }
+
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        get(&self.root, key)
+    }
+
+    pub fn get_mut<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        get_mut(&mut self.root, key)
+    }
+
+    pub fn contains_key<Q: Ord + ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    // A node with two children is replaced by the smallest node of its right subtree, which has
+    // no left child and so is easy to take out
+    pub fn remove<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let mut link = &mut self.root;
+        loop {
+            let ordering = key.cmp(link.as_ref()?.key.borrow());
+            if ordering == Ordering::Equal {
+                break;
+            }
+            let node = link.as_mut().unwrap();
+            link = if ordering == Ordering::Less {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+        }
+        let mut node = link.take().unwrap();
+        *link = match (node.left.take(), node.right.take()) {
+            (None, None) => None,
+            (Some(child), None) | (None, Some(child)) => Some(child),
+            (Some(left), Some(right)) => {
+                let (mut successor, right) = take_min(right);
+                successor.left = Some(left);
+                successor.right = right;
+                Some(successor)
+            }
+        };
+        self.len -= 1;
+        Some(node.value)
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter::new(&self.root)
+    }
+
+    pub fn pre_order(&self) -> PreOrder<'_, K, V> {
+        PreOrder {
+            stack: self.root.as_deref().into_iter().collect(),
+        }
+    }
+
+    // The entries with keys in range, in order: tree.range(10..20), tree.range(..=5)
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V, R> {
+        self::range(&self.root, range)
+    }
+
+    pub fn height(&self) -> usize {
+        height(&self.root)
+    }
+}
+
+// The smallest node of a subtree, and the subtree without it
+fn take_min<K, V>(mut root: Box<Node<K, V>>) -> (Box<Node<K, V>>, Link<K, V>) {
+    if root.left.is_none() {
+        let rest = root.right.take();
+        return (root, rest);
+    }
+    let mut link = &mut root.left;
+    while link.as_ref().unwrap().left.is_some() {
+        link = &mut link.as_mut().unwrap().left;
+    }
+    let mut min = link.take().unwrap();
+    *link = min.right.take();
+    (min, Some(root))
+}
+
+impl<K: Ord, V> Default for Bst<K, V> {
+    fn default() -> Bst<K, V> {
+        Bst::new()
+    }
+}
+
+impl<K, V> Drop for Bst<K, V> {
+    fn drop(&mut self) {
+        drop_iteratively(&mut self.root);
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Bst<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Bst<K, V> {
+        let mut tree = Bst::new();
+        for (key, value) in iter {
+            tree.insert(key, value);
+        }
+        tree
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a Bst<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+// AVL rebalancing. The tree is at most O(log n) deep, so these recurse.
+
+fn node_height<K, V>(link: &Link<K, V>) -> usize {
+    link.as_ref().map_or(0, |node| node.height)
+}
+
+fn update_height<K, V>(node: &mut Node<K, V>) {
+    node.height = 1 + node_height(&node.left).max(node_height(&node.right));
+}
+
+// positive when the left side is taller
+fn balance_factor<K, V>(node: &Node<K, V>) -> isize {
+    node_height(&node.left) as isize - node_height(&node.right) as isize
+}
+
+fn rotate_right<K, V>(link: &mut Link<K, V>) {
+    let mut node = link.take().unwrap();
+    let mut left = node.left.take().unwrap();
+    node.left = left.right.take();
+    update_height(&mut node);
+    left.right = Some(node);
+    update_height(&mut left);
+    *link = Some(left);
+}
+
+fn rotate_left<K, V>(link: &mut Link<K, V>) {
+    let mut node = link.take().unwrap();
+    let mut right = node.right.take().unwrap();
+    node.right = right.left.take();
+    update_height(&mut node);
+    right.left = Some(node);
+    update_height(&mut right);
+    *link = Some(right);
+}
+
+// Called on the way back up from an insert or remove, when the subtrees below are balanced again.
+// A child leaning the other way (left-right or right-left) is rotated first, so one rotation of
+// the node itself is enough.
+fn rebalance<K, V>(link: &mut Link<K, V>) {
+    let Some(node) = link.as_mut() else {
+        return;
+    };
+    update_height(node);
+    match balance_factor(node) {
+        2.. => {
+            if balance_factor(node.left.as_ref().unwrap()) < 0 {
+                rotate_left(&mut node.left);
+            }
+            rotate_right(link);
+        }
+        ..=-2 => {
+            if balance_factor(node.right.as_ref().unwrap()) > 0 {
+                rotate_right(&mut node.right);
+            }
+            rotate_left(link);
+        }
+        _ => {}
+    }
+}
+
+fn avl_insert<K: Ord, V>(link: &mut Link<K, V>, key: K, value: V) -> Option<V> {
+    let Some(node) = link.as_mut() else {
+        *link = Some(Node::new(key, value));
+        return None;
+    };
+    let old = match key.cmp(&node.key) {
+        Ordering::Less => avl_insert(&mut node.left, key, value),
+        Ordering::Greater => avl_insert(&mut node.right, key, value),
+        Ordering::Equal => return Some(std::mem::replace(&mut node.value, value)),
+    };
+    rebalance(link);
+    old
+}
+
+fn avl_take_min<K, V>(link: &mut Link<K, V>) -> Box<Node<K, V>> {
+    let node = link.as_mut().unwrap();
+    if node.left.is_some() {
+        let min = avl_take_min(&mut node.left);
+        rebalance(link);
+        min
+    } else {
+        let mut min = link.take().unwrap();
+        *link = min.right.take();
+        min
+    }
+}
+
+fn avl_remove<K, V, Q>(link: &mut Link<K, V>, key: &Q) -> Option<V>
+where
+    K: Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    let node = link.as_mut()?;
+    let removed = match key.cmp(node.key.borrow()) {
+        Ordering::Less => avl_remove(&mut node.left, key),
+        Ordering::Greater => avl_remove(&mut node.right, key),
+        Ordering::Equal => {
+            let mut node = link.take().unwrap();
+            *link = match (node.left.take(), node.right.take()) {
+                (None, None) => None,
+                (Some(child), None) | (None, Some(child)) => Some(child),
+                (Some(left), Some(right)) => {
+                    let mut right = Some(right);
+                    let mut successor = avl_take_min(&mut right);
+                    successor.left = Some(left);
+                    successor.right = right;
+                    Some(successor)
+                }
+            };
+            Some(node.value)
+        }
+    };
+    rebalance(link);
+    removed
+}
+
+pub struct AvlTree<K, V> {
+    root: Link<K, V>,
+    len: usize,
+}
+
+impl<K: Ord, V> AvlTree<K, V> {
+    pub fn new() -> AvlTree<K, V> {
+        AvlTree { root: None, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Returns the old value if the key was there already
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = avl_insert(&mut self.root, key, value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    pub fn get<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        get(&self.root, key)
+    }
+
+    pub fn get_mut<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        get_mut(&mut self.root, key)
+    }
+
+    pub fn contains_key<Q: Ord + ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<Q: Ord + ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let removed = avl_remove(&mut self.root, key);
+        if removed.is_some() {
+            self.len -= 1;
+        }
+        removed
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter::new(&self.root)
+    }
+
+    pub fn pre_order(&self) -> PreOrder<'_, K, V> {
+        PreOrder {
+            stack: self.root.as_deref().into_iter().collect(),
+        }
+    }
+
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V, R> {
+        self::range(&self.root, range)
+    }
+
+    // Stored in the root, no need to count
+    pub fn height(&self) -> usize {
+        node_height(&self.root)
+    }
+}
+
+impl<K: Ord, V> Default for AvlTree<K, V> {
+    fn default() -> AvlTree<K, V> {
+        AvlTree::new()
+    }
+}
+
+impl<K, V> Drop for AvlTree<K, V> {
+    fn drop(&mut self) {
+        drop_iteratively(&mut self.root);
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for AvlTree<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> AvlTree<K, V> {
+        let mut tree = AvlTree::new();
+        for (key, value) in iter {
+            tree.insert(key, value);
+        }
+        tree
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a AvlTree<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+// Checks the AVL invariants below link and returns its height: keys in order, stored heights
+// right, and no node with one side more than one taller than the other
+fn assert_avl<K: Ord, V>(link: &Link<K, V>, low: Option<&K>, high: Option<&K>) -> usize {
+    let Some(node) = link else {
+        return 0;
+    };
+    assert!(low.is_none_or(|low| *low < node.key));
+    assert!(high.is_none_or(|high| node.key < *high));
+    let left = assert_avl(&node.left, low, Some(&node.key));
+    let right = assert_avl(&node.right, Some(&node.key), high);
+    assert!(left.abs_diff(right) <= 1, "unbalanced node");
+    assert_eq!(node.height, 1 + left.max(right));
+    node.height
+}
+
+fn it_supports_map_operations() {
+    let mut tree = Bst::new();
+    assert!(tree.is_empty());
+    assert_eq!(tree.insert(String::from("kartik"), 25), None);
+    assert_eq!(tree.insert(String::from("alice"), 30), None);
+    assert_eq!(tree.insert(String::from("kartik"), 26), Some(25));
+    // String keys can be looked up with a &str, like in BTreeMap
+    assert_eq!(tree.get("kartik"), Some(&26));
+    *tree.get_mut("alice").unwrap() += 1;
+    assert_eq!(tree.get("alice"), Some(&31));
+    assert!(!tree.contains_key("bob"));
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree.remove("alice"), Some(31));
+    assert_eq!(tree.remove("alice"), None);
+    assert_eq!(tree.len(), 1);
+
+    //        50
+    //      /    \
+    //    30      70
+    //   /  \    /
+    //  20  40  60
+    //        \
+    //         45
+    let mut tree: Bst<i32, ()> = [50, 30, 70, 20, 40, 60, 45]
+        .into_iter()
+        .map(|key| (key, ()))
+        .collect();
+    assert_eq!(tree.height(), 4);
+    // two children: 30's successor 40 moves up and 45 takes 40's old place
+    tree.remove(&30);
+    assert_eq!(
+        tree.pre_order().map(|(k, _)| *k).collect::<Vec<_>>(),
+        [50, 40, 20, 45, 70, 60]
+    );
+    // the successor is deeper down: 60 is the smallest key right of 50
+    tree.remove(&50);
+    assert_eq!(
+        tree.pre_order().map(|(k, _)| *k).collect::<Vec<_>>(),
+        [60, 40, 20, 45, 70]
+    );
+    tree.remove(&70);
+    tree.remove(&20);
+    assert_eq!(
+        tree.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+        [40, 45, 60]
+    );
+
+    let mut avl = AvlTree::new();
+    avl.insert("b", 2);
+    avl.insert("a", 1);
+    *avl.get_mut("a").unwrap() = 10;
+    assert_eq!(avl.get("a"), Some(&10));
+    assert!(avl.contains_key("b"));
+    assert_eq!(avl.remove("b"), Some(2));
+    assert_eq!(avl.remove("b"), None);
+    assert_eq!(avl.len(), 1);
+    assert!(!avl.is_empty());
+}
+
+fn it_iterates_in_order_and_pre_order() {
+    // inserted in pre-order, so pre_order gives them back as they are
+    let keys = [50, 30, 20, 40, 70, 60, 80];
+    let tree: Bst<i32, i32> = keys.iter().map(|&key| (key, key * 10)).collect();
+    assert_eq!(
+        tree.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+        [20, 30, 40, 50, 60, 70, 80]
+    );
+    assert_eq!(tree.pre_order().map(|(k, _)| *k).collect::<Vec<_>>(), keys);
+    let values: Vec<i32> = (&tree).into_iter().map(|(_, v)| *v).collect();
+    assert_eq!(values, [200, 300, 400, 500, 600, 700, 800]);
+
+    // rebuilding from the pre-order gives the same tree
+    let copy: Bst<i32, i32> = tree.pre_order().map(|(k, v)| (*k, *v)).collect();
+    assert!(copy.pre_order().eq(tree.pre_order()));
+
+    // inserting 1, 2, 3 in order rotates 2 up to the root
+    let avl: AvlTree<i32, ()> = (1..=3).map(|key| (key, ())).collect();
+    assert_eq!(
+        avl.pre_order().map(|(k, _)| *k).collect::<Vec<_>>(),
+        [2, 1, 3]
+    );
+    assert_eq!((&avl).into_iter().count(), 3);
+    assert_eq!(Bst::<i32, ()>::new().iter().next(), None);
+}
+
+fn it_queries_ranges() {
+    let tree: Bst<i32, ()> = [50, 30, 70, 20, 40, 60, 80]
+        .into_iter()
+        .map(|key| (key, ()))
+        .collect();
+    fn keys<'a>(entries: impl Iterator<Item = (&'a i32, &'a ())>) -> Vec<i32> {
+        entries.map(|(k, _)| *k).collect()
+    }
+    assert_eq!(keys(tree.range(30..60)), [30, 40, 50]);
+    assert_eq!(keys(tree.range(35..=60)), [40, 50, 60]);
+    assert_eq!(keys(tree.range(..40)), [20, 30]);
+    assert_eq!(keys(tree.range(65..)), [70, 80]);
+    assert_eq!(keys(tree.range(..)).len(), 7);
+    assert!(keys(tree.range(41..49)).is_empty());
+    assert_eq!(
+        keys(tree.range((Bound::Excluded(50), Bound::Excluded(80)))),
+        [60, 70]
+    );
+
+    let avl: AvlTree<i32, ()> = (0..100).map(|key| (key, ())).collect();
+    assert_eq!(avl.range(10..15).count(), 5);
+}
+
+fn it_stays_balanced() {
+    // sorted keys: the plain tree is a list, the AVL tree as low as a tree of 10000 keys can be
+    let plain: Bst<u32, ()> = (0..10_000).map(|key| (key, ())).collect();
+    let mut avl: AvlTree<u32, ()> = (0..10_000).map(|key| (key, ())).collect();
+    assert_eq!(plain.height(), 10_000);
+    assert_eq!(avl.height(), 14);
+    assert_eq!(height(&avl.root), 14);
+    assert_avl(&avl.root, None, None);
+
+    for key in (0..10_000).step_by(3) {
+        avl.remove(&key);
+    }
+    assert_avl(&avl.root, None, None);
+    assert!(avl.height() <= 14);
+    // the plain tree is dropped here without a stack overflow
+}
+
+fn it_matches_btree_map_on_random_operations() {
+    let mut rng = XorShift(0x2545F4914F6CDD1D);
+    let mut plain: Bst<u64, u64> = Bst::new();
+    let mut avl: AvlTree<u64, u64> = AvlTree::new();
+    let mut std_map: BTreeMap<u64, u64> = BTreeMap::new();
+
+    for step in 0..20_000 {
+        let key = rng.next() % 500;
+        match rng.next() % 5 {
+            0 | 1 => {
+                let expected = std_map.insert(key, step);
+                assert_eq!(plain.insert(key, step), expected);
+                assert_eq!(avl.insert(key, step), expected);
+            }
+            2 => {
+                let expected = std_map.remove(&key);
+                assert_eq!(plain.remove(&key), expected);
+                assert_eq!(avl.remove(&key), expected);
+            }
+            3 => {
+                let end = key + rng.next() % 50;
+                let expected: Vec<_> = std_map.range(key..end).collect();
+                assert!(plain.range(key..end).eq(expected.iter().copied()));
+                assert!(avl.range(key..end).eq(expected.iter().copied()));
+            }
+            _ => {
+                let expected = std_map.get(&key);
+                assert_eq!(plain.get(&key), expected);
+                assert_eq!(avl.get(&key), expected);
+            }
+        }
+        assert_eq!(plain.len(), std_map.len());
+        assert_eq!(avl.len(), std_map.len());
+        if step % 1000 == 0 {
+            assert_avl(&avl.root, None, None);
+        }
+    }
+    assert!(plain.iter().eq(std_map.iter()));
+    assert!(avl.iter().eq(std_map.iter()));
+    assert_avl(&avl.root, None, None);
+}
@@ -0,0 +1,609 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::advance_rust::scope_guards::TempDir;
+
+// Graphs
+// A graph is a set of nodes and edges between them. Directed edges go one way (a task and the task that
+// depends on it), undirected edges both ways (two cities and the road between them). Edges can carry a
+// weight, like the length of the road.
+//
+// Nodes are stored in a Vec and known by their index in it, every node has a list of its outgoing edges
+// (an adjacency list). An undirected edge is stored in the lists of both its ends. Nodes have names so
+// graphs can be written down as text, the names are looked up in a HashMap to find the index.
+//
+// Edge list files have one edge per line, `from to [weight]` (weight 1 if left out), or just a node name
+// for a node without edges. The first line can say `directed` (the default) or `undirected`.
+// Empty lines and lines starting with # are skipped.
+//
+// cargo run -- graph <file> <query> runs a query on a file, see USAGE below.
+
+pub fn graph() {
+    let mut roads = Graph::new(Kind::Undirected);
+    roads.add_edge("amsterdam", "berlin", 650);
+    roads.add_edge("amsterdam", "paris", 500);
+    roads.add_edge("paris", "berlin", 1200);
+    roads.add_edge("berlin", "prague", 350);
+    if let Ok(Some((distance, path))) = roads.shortest_path("paris", "prague") {
+        println!("paris to prague: {} ({} km)", path.join(" -> "), distance);
+    }
+
+    it_traverses_breadth_and_depth_first();
+    it_finds_cycles();
+    it_sorts_topologically();
+    it_finds_shortest_paths();
+    it_loads_edge_list_files();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Directed,
+    Undirected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge {
+    pub to: usize,
+    pub weight: u64,
+}
+
+#[derive(Debug)]
+pub enum GraphError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    UnknownNode(String),
+    // the nodes of a cycle, the first one repeated at the end
+    Cycle(Vec<String>),
+    // the query only makes sense for directed graphs
+    Undirected,
+    // the only paths to the node weigh more than a u64 can hold
+    Overflow(String),
+    Usage(String),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::Io(err) => write!(f, "io error: {}", err),
+            GraphError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            GraphError::UnknownNode(name) => write!(f, "no node named {}", name),
+            GraphError::Cycle(nodes) => write!(f, "graph has a cycle: {}", nodes.join(" -> ")),
+            GraphError::Undirected => write!(f, "graph is undirected"),
+            GraphError::Overflow(name) => {
+                write!(f, "the paths to {} are too heavy to add up", name)
+            }
+            GraphError::Usage(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+impl From<io::Error> for GraphError {
+    fn from(err: io::Error) -> GraphError {
+        GraphError::Io(err)
+    }
+}
+
+#[derive(Debug)]
+pub struct Graph {
+    kind: Kind,
+    names: Vec<String>,
+    indices: HashMap<String, usize>,
+    adjacency: Vec<Vec<Edge>>,
+    edge_count: usize,
+}
+
+// A node of the depth first traversals with how far through its edges we are
+struct Frame {
+    node: usize,
+    next_edge: usize,
+    // undirected only: the edge back to where we came from is not a cycle, but only once, a second
+    // edge between the same two nodes is
+    parent: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Color {
+    Unvisited,
+    // on the current path
+    InProgress,
+    Done,
+}
+
+impl Graph {
+    pub fn new(kind: Kind) -> Graph {
+        Graph {
+            kind,
+            names: Vec::new(),
+            indices: HashMap::new(),
+            adjacency: Vec::new(),
+            edge_count: 0,
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Graph, GraphError> {
+        Graph::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Graph, GraphError> {
+        let mut graph: Option<Graph> = None;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let graph = match graph.as_mut() {
+                Some(graph) => graph,
+                None => match line {
+                    "directed" | "undirected" => {
+                        let kind = if line == "directed" {
+                            Kind::Directed
+                        } else {
+                            Kind::Undirected
+                        };
+                        graph = Some(Graph::new(kind));
+                        continue;
+                    }
+                    _ => graph.insert(Graph::new(Kind::Directed)),
+                },
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let parse_error = |message: String| GraphError::Parse {
+                line: number + 1,
+                message,
+            };
+            match fields.as_slice() {
+                [node] => {
+                    graph.add_node(node);
+                }
+                [from, to] => graph.add_edge(from, to, 1),
+                [from, to, weight] => {
+                    let weight = weight.parse().map_err(|_| {
+                        parse_error(format!("weight must be a whole number, got {}", weight))
+                    })?;
+                    graph.add_edge(from, to, weight);
+                }
+                _ => {
+                    return Err(parse_error(format!(
+                        "expected `from to [weight]`, got {}",
+                        line
+                    )))
+                }
+            }
+        }
+        Ok(graph.unwrap_or_else(|| Graph::new(Kind::Directed)))
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+
+    // The index of the node, adding it if there is none with that name yet
+    pub fn add_node(&mut self, name: &str) -> usize {
+        if let Some(&index) = self.indices.get(name) {
+            return index;
+        }
+        let index = self.names.len();
+        self.names.push(String::from(name));
+        self.indices.insert(String::from(name), index);
+        self.adjacency.push(Vec::new());
+        index
+    }
+
+    // Adds the nodes too if needed
+    pub fn add_edge(&mut self, from: &str, to: &str, weight: u64) {
+        let from = self.add_node(from);
+        let to = self.add_node(to);
+        self.adjacency[from].push(Edge { to, weight });
+        if self.kind == Kind::Undirected {
+            self.adjacency[to].push(Edge { to: from, weight });
+        }
+        self.edge_count += 1;
+    }
+
+    pub fn index(&self, name: &str) -> Result<usize, GraphError> {
+        self.indices
+            .get(name)
+            .copied()
+            .ok_or_else(|| GraphError::UnknownNode(String::from(name)))
+    }
+
+    pub fn name(&self, index: usize) -> &str {
+        &self.names[index]
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.names.len()
+    }
+
+    // An undirected edge counts once
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    pub fn edges(&self, index: usize) -> &[Edge] {
+        &self.adjacency[index]
+    }
+
+    fn names_of(&self, indices: impl IntoIterator<Item = usize>) -> Vec<&str> {
+        indices.into_iter().map(|index| self.name(index)).collect()
+    }
+
+    // Nodes reachable from start, nearest first
+    pub fn bfs(&self, start: &str) -> Result<Vec<&str>, GraphError> {
+        let start = self.index(start)?;
+        let mut seen = vec![false; self.node_count()];
+        let mut queue = VecDeque::from([start]);
+        let mut order = Vec::new();
+        seen[start] = true;
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            for edge in &self.adjacency[node] {
+                if !seen[edge.to] {
+                    seen[edge.to] = true;
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+        Ok(self.names_of(order))
+    }
+
+    // Nodes reachable from start in the order a recursive depth first search visits them. The
+    // recursion is replaced by a stack of frames so a long path can't overflow the call stack.
+    pub fn dfs(&self, start: &str) -> Result<Vec<&str>, GraphError> {
+        let start = self.index(start)?;
+        let mut seen = vec![false; self.node_count()];
+        let mut stack = vec![Frame {
+            node: start,
+            next_edge: 0,
+            parent: None,
+        }];
+        let mut order = vec![start];
+        seen[start] = true;
+        while let Some(frame) = stack.last_mut() {
+            let Some(edge) = self.adjacency[frame.node].get(frame.next_edge) else {
+                stack.pop();
+                continue;
+            };
+            frame.next_edge += 1;
+            if !seen[edge.to] {
+                seen[edge.to] = true;
+                order.push(edge.to);
+                stack.push(Frame {
+                    node: edge.to,
+                    next_edge: 0,
+                    parent: None,
+                });
+            }
+        }
+        Ok(self.names_of(order))
+    }
+
+    // The first cycle a depth first search runs into, as a path that ends where it started.
+    // A cycle shows up as an edge to a node that is still on the current path.
+    pub fn find_cycle(&self) -> Option<Vec<&str>> {
+        let mut color = vec![Color::Unvisited; self.node_count()];
+        for root in 0..self.node_count() {
+            if color[root] != Color::Unvisited {
+                continue;
+            }
+            color[root] = Color::InProgress;
+            let mut stack = vec![Frame {
+                node: root,
+                next_edge: 0,
+                parent: None,
+            }];
+            while let Some(frame) = stack.last_mut() {
+                let Some(edge) = self.adjacency[frame.node].get(frame.next_edge) else {
+                    color[frame.node] = Color::Done;
+                    stack.pop();
+                    continue;
+                };
+                frame.next_edge += 1;
+                if self.kind == Kind::Undirected && frame.parent == Some(edge.to) {
+                    frame.parent = None;
+                    continue;
+                }
+                match color[edge.to] {
+                    Color::Unvisited => {
+                        color[edge.to] = Color::InProgress;
+                        let parent = Some(frame.node);
+                        stack.push(Frame {
+                            node: edge.to,
+                            next_edge: 0,
+                            parent,
+                        });
+                    }
+                    Color::InProgress => {
+                        // the stack is the current path, the cycle is its part from edge.to on
+                        let start = stack.iter().position(|f| f.node == edge.to).unwrap();
+                        let cycle = stack[start..].iter().map(|f| f.node);
+                        return Some(self.names_of(cycle.chain([edge.to])));
+                    }
+                    // finished without finding a way back to the current path
+                    Color::Done => {}
+                }
+            }
+        }
+        None
+    }
+
+    // Every node after all the nodes with an edge to it (Kahn's algorithm: repeatedly take a node that
+    // nothing left points to). Fails on undirected graphs and on cycles, which have no such order.
+    pub fn topological_sort(&self) -> Result<Vec<&str>, GraphError> {
+        if self.kind == Kind::Undirected {
+            return Err(GraphError::Undirected);
+        }
+        let mut incoming = vec![0; self.node_count()];
+        for edges in &self.adjacency {
+            for edge in edges {
+                incoming[edge.to] += 1;
+            }
+        }
+        let mut ready: VecDeque<usize> = (0..self.node_count())
+            .filter(|&node| incoming[node] == 0)
+            .collect();
+        let mut order = Vec::new();
+        while let Some(node) = ready.pop_front() {
+            order.push(node);
+            for edge in &self.adjacency[node] {
+                incoming[edge.to] -= 1;
+                if incoming[edge.to] == 0 {
+                    ready.push_back(edge.to);
+                }
+            }
+        }
+        if order.len() < self.node_count() {
+            let cycle = self.find_cycle().expect("nodes left over means a cycle");
+            return Err(GraphError::Cycle(
+                cycle.into_iter().map(String::from).collect(),
+            ));
+        }
+        Ok(self.names_of(order))
+    }
+
+    // The total weight and nodes of the lightest path from one node to another, None if there is
+    // no path. Dijkstra's algorithm: always continue from the closest node not finished yet, a
+    // BinaryHeap of Reverse((distance, node)) hands them out closest first. Weights are unsigned,
+    // the algorithm is wrong with negative ones. Weights come from files, so a path can add up to
+    // more than u64::MAX: such a path is never the lightest one, it is skipped, and if it was the
+    // only way to reach `to` that's an Overflow error rather than no path.
+    pub fn shortest_path(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> Result<Option<(u64, Vec<&str>)>, GraphError> {
+        let from = self.index(from)?;
+        let to = self.index(to)?;
+        let mut distance: Vec<Option<u64>> = vec![None; self.node_count()];
+        let mut previous: Vec<Option<usize>> = vec![None; self.node_count()];
+        let mut heap = BinaryHeap::from([Reverse((0, from))]);
+        distance[from] = Some(0);
+        // nodes an edge reached only with a sum past u64::MAX
+        let mut overflowed = vec![false; self.node_count()];
+        while let Some(Reverse((dist, node))) = heap.pop() {
+            if node == to {
+                break;
+            }
+            // an outdated entry, the node was reached by a shorter path since it was pushed
+            if distance[node].is_some_and(|best| dist > best) {
+                continue;
+            }
+            for edge in &self.adjacency[node] {
+                let Some(next) = dist.checked_add(edge.weight) else {
+                    overflowed[edge.to] = true;
+                    continue;
+                };
+                if distance[edge.to].is_none_or(|best| next < best) {
+                    distance[edge.to] = Some(next);
+                    previous[edge.to] = Some(node);
+                    heap.push(Reverse((next, edge.to)));
+                }
+            }
+        }
+
+        let Some(total) = distance[to] else {
+            if overflowed[to] {
+                return Err(GraphError::Overflow(self.name(to).to_string()));
+            }
+            return Ok(None);
+        };
+        let mut path = vec![to];
+        while let Some(node) = previous[*path.last().unwrap()] {
+            path.push(node);
+        }
+        path.reverse();
+        Ok(Some((total, self.names_of(path))))
+    }
+}
+
+const USAGE: &str =
+    "usage: graph <edge list file> <bfs <start>|dfs <start>|cycle|topo|path <from> <to>>";
+
+pub fn run_cli(args: &[String]) -> Result<(), GraphError> {
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    let Some((path, query)) = args.split_first() else {
+        return Err(GraphError::Usage(String::from(USAGE)));
+    };
+    let graph = Graph::load(path)?;
+    match query {
+        ["bfs", start] => println!("{}", graph.bfs(start)?.join(" ")),
+        ["dfs", start] => println!("{}", graph.dfs(start)?.join(" ")),
+        ["cycle"] => match graph.find_cycle() {
+            Some(cycle) => println!("cycle: {}", cycle.join(" -> ")),
+            None => println!("no cycle"),
+        },
+        ["topo"] => println!("{}", graph.topological_sort()?.join(" ")),
+        ["path", from, to] => match graph.shortest_path(from, to)? {
+            Some((distance, path)) => println!("{} (distance {})", path.join(" -> "), distance),
+            None => println!("no path from {} to {}", from, to),
+        },
+        _ => return Err(GraphError::Usage(String::from(USAGE))),
+    }
+    Ok(())
+}
+
+fn it_traverses_breadth_and_depth_first() {
+    //   a -> b -> d
+    //   a -> c -> d -> e
+    let graph = Graph::parse("a b\na c\nb d\nc d\nd e\nf").unwrap();
+    assert_eq!(graph.bfs("a").unwrap(), ["a", "b", "c", "d", "e"]);
+    assert_eq!(graph.dfs("a").unwrap(), ["a", "b", "d", "e", "c"]);
+    assert_eq!(graph.dfs("d").unwrap(), ["d", "e"]);
+    assert_eq!(graph.bfs("f").unwrap(), ["f"]);
+    assert!(matches!(graph.bfs("x"), Err(GraphError::UnknownNode(name)) if name == "x"));
+
+    // the same edges undirected reach everything but f from anywhere
+    let graph = Graph::parse("undirected\na b\na c\nb d\nc d\nd e\nf").unwrap();
+    assert_eq!(graph.bfs("e").unwrap(), ["e", "d", "b", "c", "a"]);
+    assert_eq!(graph.dfs("e").unwrap(), ["e", "d", "b", "a", "c"]);
+    assert_eq!(graph.node_count(), 6);
+    assert_eq!(graph.edge_count(), 5);
+    assert_eq!(graph.edges(graph.index("d").unwrap()).len(), 3);
+}
+
+fn it_finds_cycles() {
+    let graph = Graph::parse("a b\nb c\nc d\nd b").unwrap();
+    assert_eq!(graph.find_cycle().unwrap(), ["b", "c", "d", "b"]);
+
+    // two paths meeting again is not a cycle in a directed graph
+    let graph = Graph::parse("a b\na c\nb d\nc d").unwrap();
+    assert_eq!(graph.find_cycle(), None);
+
+    // but it is in an undirected one
+    let graph = Graph::parse("undirected\na b\na c\nb d\nc d").unwrap();
+    assert_eq!(graph.find_cycle().unwrap(), ["a", "b", "d", "c", "a"]);
+
+    // going back over the edge we came in on is not a cycle, a second edge between the two is
+    let graph = Graph::parse("undirected\na b\nb c").unwrap();
+    assert_eq!(graph.find_cycle(), None);
+    let graph = Graph::parse("undirected\na b\na b").unwrap();
+    assert_eq!(graph.find_cycle().unwrap(), ["a", "b", "a"]);
+
+    let graph = Graph::parse("a a").unwrap();
+    assert_eq!(graph.find_cycle().unwrap(), ["a", "a"]);
+}
+
+fn it_sorts_topologically() {
+    // getting dressed
+    let graph = Graph::parse(
+        "underwear trousers\nunderwear shoes\nsocks shoes\ntrousers shoes\ntrousers belt\nshirt belt",
+    )
+    .unwrap();
+    let order = graph.topological_sort().unwrap();
+    assert_eq!(
+        order,
+        ["underwear", "socks", "shirt", "trousers", "shoes", "belt"]
+    );
+    let position = |name: &str| order.iter().position(|node| *node == name).unwrap();
+    for node in 0..graph.node_count() {
+        for edge in graph.edges(node) {
+            assert!(position(graph.name(node)) < position(graph.name(edge.to)));
+        }
+    }
+
+    let graph = Graph::parse("a b\nb c\nc a").unwrap();
+    let err = graph.topological_sort().unwrap_err();
+    assert_eq!(err.to_string(), "graph has a cycle: a -> b -> c -> a");
+
+    let graph = Graph::parse("undirected\na b").unwrap();
+    assert!(matches!(
+        graph.topological_sort(),
+        Err(GraphError::Undirected)
+    ));
+}
+
+fn it_finds_shortest_paths() {
+    let mut graph = Graph::new(Kind::Directed);
+    graph.add_edge("a", "b", 7);
+    graph.add_edge("a", "c", 9);
+    graph.add_edge("a", "f", 14);
+    graph.add_edge("b", "c", 10);
+    graph.add_edge("b", "d", 15);
+    graph.add_edge("c", "d", 11);
+    graph.add_edge("c", "f", 2);
+    graph.add_edge("d", "e", 6);
+    graph.add_edge("f", "e", 9);
+    graph.add_node("island");
+
+    assert_eq!(
+        graph.shortest_path("a", "e").unwrap(),
+        Some((20, vec!["a", "c", "f", "e"]))
+    );
+    assert_eq!(
+        graph.shortest_path("a", "d").unwrap(),
+        Some((20, vec!["a", "c", "d"]))
+    );
+    assert_eq!(graph.shortest_path("a", "a").unwrap(), Some((0, vec!["a"])));
+    // edges only go one way
+    assert_eq!(graph.shortest_path("e", "a").unwrap(), None);
+    assert_eq!(graph.shortest_path("a", "island").unwrap(), None);
+    assert!(graph.shortest_path("a", "nowhere").is_err());
+    assert_eq!(graph.kind(), Kind::Directed);
+
+    // weights that don't fit in a u64 when added up
+    // a -> b -> x overflows and is skipped, c is still found
+    let graph = Graph::parse("a b 18446744073709551614\nb x 5\na c 18446744073709551615").unwrap();
+    assert_eq!(
+        graph.shortest_path("a", "c").unwrap(),
+        Some((u64::MAX, vec!["a", "c"]))
+    );
+    let graph = Graph::parse("a b 18446744073709551615\nb c 5").unwrap();
+    let err = graph.shortest_path("a", "c").unwrap_err();
+    assert!(matches!(&err, GraphError::Overflow(name) if name == "c"));
+    assert_eq!(err.to_string(), "the paths to c are too heavy to add up");
+    assert_eq!(
+        graph.shortest_path("a", "b").unwrap(),
+        Some((u64::MAX, vec!["a", "b"]))
+    );
+    // an overflow elsewhere doesn't turn nodes without any path into errors
+    let graph = Graph::parse("a b 18446744073709551615\nb c 5\nd\ne a 1").unwrap();
+    assert_eq!(graph.shortest_path("a", "d").unwrap(), None);
+    assert_eq!(graph.shortest_path("a", "e").unwrap(), None);
+    assert!(graph.shortest_path("a", "c").is_err());
+}
+
+fn it_loads_edge_list_files() {
+    let dir = TempDir::new("rust-notes-graph").unwrap();
+    let path = dir.path().join("roads.txt");
+    fs::write(
+        &path,
+        "# roads, in km\nundirected\n\namsterdam berlin 650\namsterdam paris 500\n  paris berlin 1200\nberlin prague 350\n",
+    )
+    .unwrap();
+
+    let graph = Graph::load(&path).unwrap();
+    assert_eq!(graph.kind(), Kind::Undirected);
+    assert_eq!(
+        graph.shortest_path("prague", "paris").unwrap(),
+        Some((1500, vec!["prague", "berlin", "amsterdam", "paris"]))
+    );
+
+    let err = Graph::parse("a b\na b c").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 2: weight must be a whole number, got c"
+    );
+    let err = Graph::parse("directed\n\na b c d").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "line 3: expected `from to [weight]`, got a b c d"
+    );
+    assert!(matches!(
+        Graph::load(dir.path().join("missing.txt")),
+        Err(GraphError::Io(_))
+    ));
+    assert_eq!(Graph::parse("# nothing\n").unwrap().node_count(), 0);
+
+    let args: Vec<String> = [path.to_str().unwrap(), "path", "paris", "prague"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    run_cli(&args).unwrap();
+    assert!(matches!(run_cli(&args[..2]), Err(GraphError::Usage(_))));
+}
@@ -0,0 +1,5 @@
+pub mod bench;
+pub mod bst;
+pub mod graph;
+pub mod my_hash_map;
+pub mod my_vec;
@@ -0,0 +1,661 @@
+use std::borrow::Borrow;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+use std::ops::Index;
+
+use crate::collections::bench::XorShift;
+
+// Our own HashMap<K, V>
+// std's HashMap stores key value pairs in one flat array of slots and uses the hash of the key to pick a slot.
+// Two keys can want the same slot, this map resolves that with open addressing: if the slot is taken we
+// keep walking to the next one (linear probing) until we find the key or an empty slot.
+
+// Robin Hood hashing
+// Every entry remembers how far it had to walk from its home slot (its probe distance). While inserting,
+// if the entry we carry is further from home than the one sitting in the slot, they swap places and we
+// carry on with the evicted one. "Take from the rich, give to the poor" keeps every probe sequence short,
+// and it lets a lookup stop early: once it reaches an entry that is closer to home than the lookup has
+// walked, the key cannot be further along.
+//
+// remove uses backward shift deletion: entries after the removed one move one slot back until we hit an
+// empty slot or an entry already in its home slot, so no tombstones are needed.
+
+// The table is kept at most 7/8 full and its size is always a power of two, so hash & mask picks a slot.
+const MIN_CAPACITY: usize = 8;
+
+struct Slot<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+pub struct MyHashMap<K, V, S = RandomState> {
+    slots: Vec<Option<Slot<K, V>>>,
+    len: usize,
+    hash_builder: S,
+}
+
+impl<K, V> MyHashMap<K, V, RandomState> {
+    pub fn new() -> MyHashMap<K, V, RandomState> {
+        MyHashMap::with_hasher(RandomState::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> MyHashMap<K, V, RandomState> {
+        let mut map = MyHashMap::new();
+        map.resize(slots_for(capacity));
+        map
+    }
+}
+
+// Smallest power of two table that holds len entries without going over 7/8 full
+fn slots_for(len: usize) -> usize {
+    let needed = len.checked_mul(8).expect("capacity overflow") / 7 + 1;
+    needed.next_power_of_two().max(MIN_CAPACITY)
+}
+
+impl<K, V, S> MyHashMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> MyHashMap<K, V, S> {
+        MyHashMap {
+            slots: Vec::new(),
+            len: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Number of entries the map can hold before it has to grow
+    pub fn capacity(&self) -> usize {
+        self.slots.len() * 7 / 8
+    }
+
+    // Drops every entry but keeps the table, just like HashMap::clear
+    pub fn clear(&mut self) {
+        for slot in self.slots.iter_mut() {
+            *slot = None;
+        }
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.slots.iter(),
+            remaining: self.len,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            slots: self.slots.iter_mut(),
+            remaining: self.len,
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    fn mask(&self) -> usize {
+        self.slots.len() - 1
+    }
+
+    // How far the entry in slot index is from the slot its hash points at
+    fn probe_distance(&self, hash: u64, index: usize) -> usize {
+        index.wrapping_sub(hash as usize) & self.mask()
+    }
+
+    fn resize(&mut self, new_slots: usize) {
+        let old = mem::take(&mut self.slots);
+        self.slots.resize_with(new_slots, || None);
+        for slot in old.into_iter().flatten() {
+            self.place(slot);
+        }
+    }
+
+    // Robin Hood insertion of an entry whose key is known not to be in the map.
+    // Returns the index the entry ended up in.
+    fn place(&mut self, mut carry: Slot<K, V>) -> usize {
+        let mask = self.mask();
+        let mut index = carry.hash as usize & mask;
+        let mut dist = 0;
+        let mut placed_at = None;
+
+        loop {
+            let existing_dist = match &self.slots[index] {
+                None => {
+                    self.slots[index] = Some(carry);
+                    return placed_at.unwrap_or(index);
+                }
+                Some(existing) => self.probe_distance(existing.hash, index),
+            };
+
+            if existing_dist < dist {
+                // the resident is closer to home than we are, it moves on instead of us
+                let resident = self.slots[index].as_mut().unwrap();
+                mem::swap(resident, &mut carry);
+                placed_at.get_or_insert(index);
+                dist = existing_dist;
+            }
+            index = (index + 1) & mask;
+            dist += 1;
+        }
+    }
+}
+
+impl<K, V, S> MyHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn grow_if_full(&mut self) {
+        if self.slots.is_empty() {
+            self.resize(MIN_CAPACITY);
+        } else if (self.len + 1) * 8 > self.slots.len() * 7 {
+            self.resize(self.slots.len() * 2);
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = slots_for(self.len + additional);
+        if needed > self.slots.len() {
+            self.resize(needed);
+        }
+    }
+
+    fn find<Q>(&self, hash: u64, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let mask = self.mask();
+        let mut index = hash as usize & mask;
+        let mut dist = 0;
+
+        loop {
+            match &self.slots[index] {
+                None => return None,
+                Some(slot) => {
+                    // anything further along would have evicted this slot, so the key is not here
+                    if self.probe_distance(slot.hash, index) < dist {
+                        return None;
+                    }
+                    if slot.hash == hash && slot.key.borrow() == key {
+                        return Some(index);
+                    }
+                }
+            }
+            index = (index + 1) & mask;
+            dist += 1;
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash_builder.hash_one(&key);
+        if let Some(index) = self.find(hash, &key) {
+            let slot = self.slots[index].as_mut().unwrap();
+            return Some(mem::replace(&mut slot.value, value));
+        }
+
+        self.grow_if_full();
+        self.place(Slot { hash, key, value });
+        self.len += 1;
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.find(hash, key)?;
+        self.slots[index].as_ref().map(|slot| &slot.value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.find(hash, key)?;
+        self.slots[index].as_mut().map(|slot| &mut slot.value)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hash_builder.hash_one(key);
+        let index = self.find(hash, key)?;
+        Some(self.remove_at(index).value)
+    }
+
+    fn remove_at(&mut self, index: usize) -> Slot<K, V> {
+        let mask = self.mask();
+        let removed = self.slots[index].take().unwrap();
+        self.len -= 1;
+
+        // backward shift: pull the following entries one slot closer to home
+        let mut hole = index;
+        loop {
+            let next = (hole + 1) & mask;
+            match &self.slots[next] {
+                Some(slot) if self.probe_distance(slot.hash, next) > 0 => {
+                    self.slots[hole] = self.slots[next].take();
+                    hole = next;
+                }
+                _ => return removed,
+            }
+        }
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = self.hash_builder.hash_one(&key);
+        match self.find(hash, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                hash,
+                key,
+            }),
+        }
+    }
+}
+
+impl<K, V> Default for MyHashMap<K, V, RandomState> {
+    fn default() -> MyHashMap<K, V, RandomState> {
+        MyHashMap::new()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for MyHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, Q, V, S> Index<&Q> for MyHashMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found in MyHashMap")
+    }
+}
+
+impl<K: Hash + Eq, V> FromIterator<(K, V)> for MyHashMap<K, V, RandomState> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> MyHashMap<K, V, RandomState> {
+        let mut map = MyHashMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Extend<(K, V)> for MyHashMap<K, V, S> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+// Entry API
+// map.entry(key) looks the key up once and remembers where it is (or where it would go),
+// so "insert if missing, otherwise update" does not hash and probe twice.
+
+pub enum Entry<'a, K, V, S> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+pub struct OccupiedEntry<'a, K, V, S> {
+    map: &'a mut MyHashMap<K, V, S>,
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K, V, S> {
+    map: &'a mut MyHashMap<K, V, S>,
+    hash: u64,
+    key: K,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> Entry<'a, K, V, S> {
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Entry<'a, K, V, S> {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => &entry.key,
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> OccupiedEntry<'a, K, V, S> {
+    fn slot(&self) -> &Slot<K, V> {
+        self.map.slots[self.index].as_ref().unwrap()
+    }
+
+    fn slot_mut(&mut self) -> &mut Slot<K, V> {
+        self.map.slots[self.index].as_mut().unwrap()
+    }
+
+    pub fn key(&self) -> &K {
+        &self.slot().key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.slot().value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.slot_mut().value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.slots[self.index].as_mut().unwrap().value
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.map.remove_at(self.index).value
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher> VacantEntry<'a, K, V, S> {
+    pub fn insert(self, value: V) -> &'a mut V {
+        // growing moves every entry, so the slot is only picked after the table has room
+        self.map.grow_if_full();
+        let index = self.map.place(Slot {
+            hash: self.hash,
+            key: self.key,
+            value,
+        });
+        self.map.len += 1;
+        &mut self.map.slots[index].as_mut().unwrap().value
+    }
+}
+
+// Iterators walk the slot array and skip the empty slots, so the order depends on the hashes
+
+pub struct Iter<'a, K, V> {
+    slots: std::slice::Iter<'a, Option<Slot<K, V>>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let slot = self.slots.by_ref().flatten().next()?;
+        self.remaining -= 1;
+        Some((&slot.key, &slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+pub struct IterMut<'a, K, V> {
+    slots: std::slice::IterMut<'a, Option<Slot<K, V>>>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let slot = self.slots.by_ref().flatten().next()?;
+        self.remaining -= 1;
+        Some((&slot.key, &mut slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+pub struct IntoIter<K, V> {
+    slots: std::vec::IntoIter<Option<Slot<K, V>>>,
+    remaining: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let slot = self.slots.by_ref().flatten().next()?;
+        self.remaining -= 1;
+        Some((slot.key, slot.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V, S> IntoIterator for MyHashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            remaining: self.len,
+            slots: self.slots.into_iter(),
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a MyHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut MyHashMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+pub fn my_hash_map() {
+    it_supports_the_users_operations();
+    it_uses_the_entry_api();
+    it_iterates_over_every_entry();
+    it_matches_std_hash_map_on_random_operations();
+    it_survives_heavy_collisions();
+}
+
+fn it_supports_the_users_operations() {
+    // the same steps as the HashMap section in main
+    let mut users: MyHashMap<String, i32> = MyHashMap::new();
+    users.insert(String::from("Kartik"), 22);
+    users.insert(String::from("Ashvin"), 23);
+    assert_eq!(users.len(), 2);
+
+    assert_eq!(users.get("Kartik"), Some(&22));
+    assert_eq!(users["Ashvin"], 23);
+    assert_eq!(users.insert(String::from("Kartik"), 24), Some(22));
+
+    assert_eq!(users.remove("Kartik"), Some(24));
+    assert_eq!(users.remove("Kartik"), None);
+    assert_eq!(format!("{:?}", users), "{\"Ashvin\": 23}");
+
+    users.clear();
+    assert!(users.is_empty());
+    assert_eq!(users.get("Ashvin"), None);
+}
+
+fn it_uses_the_entry_api() {
+    let mut counts: MyHashMap<&str, usize> = MyHashMap::new();
+    for word in "a b a c b a".split(' ') {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    assert_eq!(counts["a"], 3);
+    assert_eq!(counts["b"], 2);
+    assert_eq!(counts["c"], 1);
+
+    counts
+        .entry("a")
+        .and_modify(|count| *count *= 10)
+        .or_default();
+    counts
+        .entry("d")
+        .and_modify(|count| *count *= 10)
+        .or_default();
+    assert_eq!(counts["a"], 30);
+    assert_eq!(counts["d"], 0);
+
+    match counts.entry("b") {
+        Entry::Occupied(entry) => assert_eq!(entry.remove(), 2),
+        Entry::Vacant(_) => panic!("b should be present"),
+    }
+    assert!(!counts.contains_key("b"));
+
+    // vacant inserts that trigger a resize still hand back the right value
+    let mut squares: MyHashMap<u32, u32> = MyHashMap::new();
+    for i in 0..1000 {
+        let value = squares.entry(i).or_insert_with(|| i * i);
+        assert_eq!(*value, i * i);
+    }
+    assert_eq!(squares.len(), 1000);
+}
+
+fn it_iterates_over_every_entry() {
+    let mut map: MyHashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+    for (_, value) in map.iter_mut() {
+        *value += 1;
+    }
+
+    let mut pairs: Vec<(u32, u32)> = map.iter().map(|(k, v)| (*k, *v)).collect();
+    pairs.sort();
+    assert_eq!(pairs.len(), 100);
+    assert!(pairs.iter().all(|(k, v)| *v == k + 1));
+    assert_eq!(map.iter().len(), 100);
+    assert_eq!(map.keys().sum::<u32>(), 4950);
+    assert_eq!(map.values().sum::<u32>(), 5050);
+
+    let mut owned: Vec<(u32, u32)> = map.into_iter().collect();
+    owned.sort();
+    assert_eq!(owned, pairs);
+}
+
+fn it_matches_std_hash_map_on_random_operations() {
+    let mut rng = XorShift(0x2545F4914F6CDD1D);
+    let mut mine: MyHashMap<u64, u64> = MyHashMap::new();
+    let mut std_map: std::collections::HashMap<u64, u64> = std::collections::HashMap::new();
+
+    for step in 0..20_000 {
+        let key = rng.next() % 500;
+        match rng.next() % 4 {
+            0 | 1 => assert_eq!(mine.insert(key, step), std_map.insert(key, step)),
+            2 => assert_eq!(mine.remove(&key), std_map.remove(&key)),
+            _ => assert_eq!(mine.get(&key), std_map.get(&key)),
+        }
+        assert_eq!(mine.len(), std_map.len());
+    }
+    for (key, value) in &std_map {
+        assert_eq!(mine.get(key), Some(value));
+    }
+}
+
+// Every key hashes to the same value, so everything collides and lands in one long probe run
+#[derive(Default, Clone)]
+struct ConstantHasher;
+
+impl std::hash::Hasher for ConstantHasher {
+    fn finish(&self) -> u64 {
+        7
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {}
+}
+
+impl BuildHasher for ConstantHasher {
+    type Hasher = ConstantHasher;
+
+    fn build_hasher(&self) -> ConstantHasher {
+        ConstantHasher
+    }
+}
+
+fn it_survives_heavy_collisions() {
+    let mut map: MyHashMap<u32, u32, ConstantHasher> = MyHashMap::with_hasher(ConstantHasher);
+    for i in 0..200 {
+        map.insert(i, i);
+    }
+    for i in (0..200).step_by(2) {
+        assert_eq!(map.remove(&i), Some(i));
+    }
+    for i in 0..200 {
+        assert_eq!(map.get(&i), if i % 2 == 0 { None } else { Some(&i) });
+    }
+    assert_eq!(map.len(), 100);
+}
@@ -0,0 +1,513 @@
+use std::alloc::{self, Layout};
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+use std::rc::Rc;
+
+use crate::test_support::catch_quiet_panic;
+
+// Our own Vec<T>
+// A vector is three words on the stack: a pointer to a heap buffer, the capacity of that buffer
+// and the number of elements that are actually initialised (len).
+//
+//   stack                heap
+//   ptr ------------->  [ 1 | 2 | 4 | 6 | ? | ? | ? | ? ]
+//   cap = 8               <---- len = 4 ---->
+//
+// push writes into the first free slot and only reallocates when len == cap. The buffer doubles
+// every time it grows, so pushing n elements copies at most about 2n elements in total.
+// pop and remove hand the element back to the caller, the slot just becomes uninitialised again.
+
+// Zero sized types
+// For a type like () there is nothing to store, so we never allocate. The capacity is usize::MAX
+// and the pointer is a dangling but well aligned one, which is all a zero sized read or write needs.
+
+// RawBuf only owns the allocation, it never drops elements. Keeping it separate means the memory
+// is freed by RawBuf's own Drop even when dropping one of the elements panics half way through.
+struct RawBuf<T> {
+    ptr: NonNull<T>,
+    cap: usize,
+    _marker: PhantomData<T>,
+}
+
+// NonNull is neither Send nor Sync, but a buffer of T is as thread safe as T is
+unsafe impl<T: Send> Send for RawBuf<T> {}
+unsafe impl<T: Sync> Sync for RawBuf<T> {}
+
+impl<T> RawBuf<T> {
+    fn new() -> RawBuf<T> {
+        let cap = if mem::size_of::<T>() == 0 {
+            usize::MAX
+        } else {
+            0
+        };
+        RawBuf {
+            ptr: NonNull::dangling(),
+            cap,
+            _marker: PhantomData,
+        }
+    }
+
+    fn grow(&mut self) {
+        // the capacity of a zero sized buffer is already usize::MAX, growing means len overflowed
+        assert!(mem::size_of::<T>() != 0, "capacity overflow");
+        let new_cap = if self.cap == 0 {
+            4
+        } else {
+            self.cap.checked_mul(2).expect("capacity overflow")
+        };
+        self.grow_to(new_cap);
+    }
+
+    fn grow_to(&mut self, new_cap: usize) {
+        if mem::size_of::<T>() == 0 || new_cap <= self.cap {
+            return;
+        }
+
+        // Layout::array refuses sizes above isize::MAX, the most any allocation may have
+        let new_layout = Layout::array::<T>(new_cap).expect("capacity overflow");
+        let new_ptr = if self.cap == 0 {
+            unsafe { alloc::alloc(new_layout) }
+        } else {
+            let old_layout = Layout::array::<T>(self.cap).unwrap();
+            unsafe { alloc::realloc(self.ptr.as_ptr() as *mut u8, old_layout, new_layout.size()) }
+        };
+
+        self.ptr = match NonNull::new(new_ptr as *mut T) {
+            Some(ptr) => ptr,
+            None => alloc::handle_alloc_error(new_layout),
+        };
+        self.cap = new_cap;
+    }
+}
+
+impl<T> Drop for RawBuf<T> {
+    fn drop(&mut self) {
+        if self.cap != 0 && mem::size_of::<T>() != 0 {
+            unsafe {
+                alloc::dealloc(
+                    self.ptr.as_ptr() as *mut u8,
+                    Layout::array::<T>(self.cap).unwrap(),
+                );
+            }
+        }
+    }
+}
+
+pub struct MyVec<T> {
+    buf: RawBuf<T>,
+    len: usize,
+}
+
+impl<T> MyVec<T> {
+    pub fn new() -> MyVec<T> {
+        MyVec {
+            buf: RawBuf::new(),
+            len: 0,
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> MyVec<T> {
+        let mut vec = MyVec::new();
+        vec.buf.grow_to(capacity);
+        vec
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.cap
+    }
+
+    fn ptr(&self) -> *mut T {
+        self.buf.ptr.as_ptr()
+    }
+
+    pub fn push(&mut self, elem: T) {
+        if self.len == self.buf.cap {
+            self.buf.grow();
+        }
+        unsafe {
+            ptr::write(self.ptr().add(self.len), elem);
+        }
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        unsafe { Some(ptr::read(self.ptr().add(self.len))) }
+    }
+
+    // Shifts everything after index one slot to the right
+    pub fn insert(&mut self, index: usize, elem: T) {
+        assert!(
+            index <= self.len,
+            "insertion index (is {}) should be <= len (is {})",
+            index,
+            self.len
+        );
+        if self.len == self.buf.cap {
+            self.buf.grow();
+        }
+        unsafe {
+            let slot = self.ptr().add(index);
+            ptr::copy(slot, slot.add(1), self.len - index);
+            ptr::write(slot, elem);
+        }
+        self.len += 1;
+    }
+
+    // Shifts everything after index one slot to the left
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(
+            index < self.len,
+            "removal index (is {}) should be < len (is {})",
+            index,
+            self.len
+        );
+        unsafe {
+            self.len -= 1;
+            let slot = self.ptr().add(index);
+            let elem = ptr::read(slot);
+            ptr::copy(slot.add(1), slot, self.len - index);
+            elem
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let elems: *mut [T] = &mut **self;
+        // len goes to zero first, if a drop panics the vector is empty rather than holding dropped values
+        self.len = 0;
+        unsafe {
+            ptr::drop_in_place(elems);
+        }
+    }
+}
+
+impl<T> Default for MyVec<T> {
+    fn default() -> MyVec<T> {
+        MyVec::new()
+    }
+}
+
+impl<T> Drop for MyVec<T> {
+    fn drop(&mut self) {
+        // drop_in_place on a slice keeps dropping the remaining elements if one of them panics,
+        // and self.buf is dropped after this function either way, so the allocation is never leaked
+        unsafe {
+            ptr::drop_in_place(&mut **self as *mut [T]);
+        }
+    }
+}
+
+// Deref to a slice gives us len, is_empty, indexing, iter, sort and the rest of the slice API for free
+impl<T> Deref for MyVec<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for MyVec<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr(), self.len) }
+    }
+}
+
+impl<T: Clone> Clone for MyVec<T> {
+    fn clone(&self) -> MyVec<T> {
+        let mut vec = MyVec::with_capacity(self.len);
+        for elem in self.iter() {
+            vec.push(elem.clone());
+        }
+        vec
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MyVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for MyVec<T> {
+    fn eq(&self, other: &MyVec<T>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T> FromIterator<T> for MyVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> MyVec<T> {
+        let mut vec = MyVec::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<T> Extend<T> for MyVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+// Owning iterator, moves the elements out one by one
+pub struct IntoIter<T> {
+    buf: RawBuf<T>,
+    start: usize,
+    end: usize,
+}
+
+impl<T> IntoIterator for MyVec<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        // take the buffer out without running MyVec's Drop, the iterator owns the elements now
+        let vec = ManuallyDrop::new(self);
+        let buf = unsafe { ptr::read(&vec.buf) };
+        IntoIter {
+            buf,
+            start: 0,
+            end: vec.len,
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        let elem = unsafe { ptr::read(self.buf.ptr.as_ptr().add(self.start)) };
+        self.start += 1;
+        Some(elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.start;
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.end -= 1;
+        unsafe { Some(ptr::read(self.buf.ptr.as_ptr().add(self.end))) }
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> Drop for IntoIter<T> {
+    fn drop(&mut self) {
+        // only the elements that were never yielded are still ours to drop
+        unsafe {
+            let remaining = ptr::slice_from_raw_parts_mut(
+                self.buf.ptr.as_ptr().add(self.start),
+                self.end - self.start,
+            );
+            ptr::drop_in_place(remaining);
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a MyVec<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> std::slice::Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut MyVec<T> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> std::slice::IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+pub fn my_vec() {
+    it_pushes_pops_inserts_and_removes();
+    it_grows_by_doubling();
+    it_handles_zero_sized_types();
+    it_drops_every_element_exactly_once();
+    it_survives_a_panicking_drop();
+    it_panics_on_out_of_bounds_index();
+}
+
+// Counts drops through a shared counter, optionally panicking in its own drop
+struct DropCounter {
+    drops: Rc<Cell<usize>>,
+    panic_on_drop: bool,
+}
+
+impl DropCounter {
+    fn new(drops: &Rc<Cell<usize>>) -> DropCounter {
+        DropCounter {
+            drops: Rc::clone(drops),
+            panic_on_drop: false,
+        }
+    }
+}
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+        if self.panic_on_drop {
+            panic!("DropCounter asked to panic");
+        }
+    }
+}
+
+fn it_pushes_pops_inserts_and_removes() {
+    let mut vec = MyVec::new();
+    vec.push(1);
+    vec.push(2);
+    vec.push(4);
+    vec.push(6);
+    assert_eq!(*vec, [1, 2, 4, 6]);
+
+    assert_eq!(vec.pop(), Some(6));
+    assert_eq!(vec.remove(1), 2);
+    assert_eq!(*vec, [1, 4]);
+
+    vec.insert(0, 0);
+    vec.insert(3, 9);
+    vec.insert(2, 3);
+    assert_eq!(*vec, [0, 1, 3, 4, 9]);
+
+    // everything a slice can do works through Deref
+    vec.sort_by(|a, b| b.cmp(a));
+    assert_eq!(vec[0], 9);
+    assert_eq!(vec.iter().sum::<i32>(), 17);
+    assert!(vec.contains(&3));
+
+    let doubled: MyVec<i32> = vec.into_iter().rev().map(|x| x * 2).collect();
+    assert_eq!(format!("{:?}", doubled), "[0, 2, 6, 8, 18]");
+
+    let mut empty: MyVec<String> = MyVec::new();
+    assert_eq!(empty.pop(), None);
+    assert!(empty.is_empty());
+}
+
+fn it_grows_by_doubling() {
+    let mut vec = MyVec::new();
+    assert_eq!(vec.capacity(), 0);
+    let mut capacities = vec![];
+    for i in 0..17 {
+        vec.push(i);
+        if capacities.last() != Some(&vec.capacity()) {
+            capacities.push(vec.capacity());
+        }
+    }
+    assert_eq!(capacities, [4, 8, 16, 32]);
+
+    let vec: MyVec<u64> = MyVec::with_capacity(10);
+    assert_eq!(vec.capacity(), 10);
+    assert_eq!(vec.len(), 0);
+}
+
+fn it_handles_zero_sized_types() {
+    let mut vec = MyVec::new();
+    assert_eq!(vec.capacity(), usize::MAX);
+    for _ in 0..1000 {
+        vec.push(());
+    }
+    vec.insert(500, ());
+    assert_eq!(vec.remove(0), ());
+    assert_eq!(vec.len(), 1000);
+    assert_eq!(vec.pop(), Some(()));
+    assert_eq!(vec.into_iter().count(), 999);
+
+    // a zero sized type with a Drop still has to be dropped
+    let drops = Rc::new(Cell::new(0));
+    struct Marker(Rc<Cell<usize>>);
+    impl Drop for Marker {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+    let mut markers = MyVec::new();
+    for _ in 0..10 {
+        markers.push(Marker(Rc::clone(&drops)));
+    }
+    drop(markers);
+    assert_eq!(drops.get(), 10);
+}
+
+fn it_drops_every_element_exactly_once() {
+    let drops = Rc::new(Cell::new(0));
+    let mut vec = MyVec::new();
+    for _ in 0..10 {
+        vec.push(DropCounter::new(&drops));
+    }
+
+    drop(vec.pop());
+    drop(vec.remove(3));
+    assert_eq!(drops.get(), 2);
+
+    // a half consumed iterator drops what it did not hand out
+    let mut iter = vec.into_iter();
+    drop(iter.next());
+    drop(iter.next_back());
+    assert_eq!(drops.get(), 4);
+    drop(iter);
+    assert_eq!(drops.get(), 10);
+
+    let mut vec = MyVec::new();
+    for _ in 0..5 {
+        vec.push(DropCounter::new(&drops));
+    }
+    vec.clear();
+    assert_eq!(drops.get(), 15);
+    assert!(vec.is_empty());
+}
+
+fn it_survives_a_panicking_drop() {
+    let drops = Rc::new(Cell::new(0));
+    let mut vec = MyVec::new();
+    for i in 0..5 {
+        let mut elem = DropCounter::new(&drops);
+        elem.panic_on_drop = i == 1;
+        vec.push(elem);
+    }
+
+    // the panic escapes, but the four other elements are still dropped, each exactly once
+    assert!(catch_quiet_panic(move || drop(vec)).is_some());
+    assert_eq!(drops.get(), 5);
+
+    let mut vec = MyVec::new();
+    for i in 0..5 {
+        let mut elem = DropCounter::new(&drops);
+        elem.panic_on_drop = i == 3;
+        vec.push(elem);
+    }
+    let mut iter = vec.into_iter();
+    drop(iter.next());
+    assert!(catch_quiet_panic(move || drop(iter)).is_some());
+    assert_eq!(drops.get(), 10);
+}
+
+fn it_panics_on_out_of_bounds_index() {
+    let mut vec: MyVec<i32> = (0..3).collect();
+    assert!(catch_quiet_panic(|| {
+        vec.remove(3);
+    }).is_some());
+    assert!(catch_quiet_panic(|| vec.insert(5, 1)).is_some());
+    assert_eq!(*vec, [0, 1, 2]);
+}
@@ -41,9 +41,35 @@ use std::{
     thread::{self, spawn},
 };
 mod advance_rust;
+mod collections;
+mod test_support;
+mod traits;
+mod users;
 
 #[tokio::main]
 async fn main() {
+    // cargo run -- <command> runs one of the tools instead of the notes
+    // users <command>  manages the persistent user registry
+    // bench-map [keys] compares collections::my_hash_map with std's HashMap (use --release)
+    // graph <file> <query> runs bfs, dfs, cycle, topo or path queries on an edge list file
+    // bench-arena [len] compares advance_rust::arena's ArenaList with the Rc List (use --release,
+    //   and --features count-allocations to count allocations too)
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 {
+        let result = match args[1].as_str() {
+            "users" => users::store::run_cli(&args[2..]).map_err(|err| err.to_string()),
+            "bench-map" => collections::bench::run_cli(&args[2..]),
+            "graph" => collections::graph::run_cli(&args[2..]).map_err(|err| err.to_string()),
+            "bench-arena" => advance_rust::arena::run_cli(&args[2..]),
+            other => Err(format!("unknown command {}", other)),
+        };
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let x: i8 = -40;
     let y: u8 = 30;
     let z: f32 = 20.202020;
@@ -278,6 +304,9 @@ async fn main() {
         user1.username, user1.active, user1.email
     );
 
+    // A struct literal does not check anything, any string is accepted as an email.
+    // users::builder builds a UserRecord through a builder that validates every field first.
+
     // Implementing structs
     // One can attach functions to instances of structs
 
@@ -414,6 +443,7 @@ async fn main() {
 
     // Vectors --> similar to vectors in c++ a dynamic array where we can push and pop elements
     // it allows us to store more than one value in a single data structure that puts all the values next to each other in memory contigous allocation of memory.
+    // collections::my_vec builds the same thing by hand on top of std::alloc to show what Vec does underneath.
 
     println!("Vectors----------");
     let mut vec = Vec::new();
@@ -452,6 +482,7 @@ async fn main() {
     // stores a key value pair in rust, similar to map in C++, Dict in python
     // Methods --> insert, get, remove, clear
     // use std::collections::HashMap;
+    // collections::my_hash_map is a hand written open addressing version with the same methods.
 
     let mut users: HashMap<String, i32> = HashMap::new();
     users.insert(String::from("Kartik"), 22);
@@ -691,8 +722,23 @@ async fn main() {
     };
     println!("{}", user.summarize());
 
-    noftiy(&user);
-    noftiy(&String::from("kartik"));
+    // noftiy(&user);
+    // noftiy(&String::from("kartik"));
+
+    // traits::notifier sends summaries to the subscribers of a topic, here one printing to stdout
+    let mut notifier = Notifier::new();
+    notifier.subscribe_boxed(
+        "users",
+        MessengerSubscriber::new(WriteMessenger::stdout(), Priority::Low),
+    );
+    notifier.publish("users", Priority::Normal, &user);
+    notifier.publish("users", Priority::Low, &String::from("kartik"));
+
+    // a Vec<Box<dyn Summary>> holds users, posts and anything else implementing Summary together
+    let mut feed = Feed::new();
+    feed.push(Post::new("kartik", "traits are like interfaces", 1_714_555_800));
+    feed.push(user);
+    print!("{}", feed.render());
 
     // item: impl Summary
     // is syntax sugar for a longer form known as trait bound
@@ -1025,6 +1071,30 @@ async fn main() {
     // }
 
     advance_rust::smart_pointers::smart_pointers();
+    advance_rust::weak_pointers::weak_pointers();
+    advance_rust::leak_detector::leak_detector();
+    advance_rust::my_rc::my_rc();
+    advance_rust::my_ref_cell::my_ref_cell();
+    advance_rust::drop_tracer::drop_tracer();
+    advance_rust::scope_guards::scope_guards();
+    advance_rust::messengers::messengers();
+    advance_rust::quota_service::quota_service();
+    advance_rust::rate_limits::rate_limits();
+    advance_rust::async_messengers::async_messengers().await;
+    advance_rust::mock::mock();
+    advance_rust::arena::arena();
+
+    users::store::user_store();
+    users::backend::user_backends();
+    users::builder::user_builder();
+    users::json::json();
+
+    collections::my_vec::my_vec();
+    collections::my_hash_map::my_hash_map();
+    collections::graph::graph();
+    collections::bst::bst();
+    traits::summary::summary();
+    traits::notifier::notifier();
 
 
 }
@@ -1067,7 +1137,11 @@ fn longest<'a>(a: &'a str, b: &'a str) -> &'a str {
     }
 }
 
+// #[derive(Summary)] writes the impl Summary below for us, see the summary_derive crate
+#[derive(Summary)]
+#[summary(format = "User {name} is {age} years old")]
 struct User2 {
+    #[summary(author)]
     name: String,
     age: u32,
 }
@@ -1079,11 +1153,18 @@ struct User2 {
 // }
 
 // default trait
-pub trait Summary {
-    fn summarize(&self) -> String {
-        return String::from("summarize");
-    }
-}
+// pub trait Summary {
+//     fn summarize(&self) -> String {
+//         return String::from("summarize");
+//     }
+// }
+
+// traits::summary has the full trait: a required summarize_author and a default summarize that calls it
+use traits::summary::{Feed, Post, Summary};
+// the derive macro has the same name as the trait, like serde's Serialize, they live in different namespaces
+use summary_derive::Summary;
+use advance_rust::messengers::WriteMessenger;
+use traits::notifier::{MessengerSubscriber, Notifier, Priority};
 
 // implement summary trait for user2
 // we can simply say user2 struct implements summary trait
@@ -1094,14 +1175,22 @@ pub trait Summary {
 // }
 
 // for default summary trait
-impl Summary for User2 {}
-impl Summary for String {}
+// impl Summary for User2 {}
+// impl Summary for String {}
+
+// only the required method, summarize comes from the default
+// impl Summary for User2 {
+//     fn summarize_author(&self) -> String {
+//         format!("{} ({})", self.name, self.age)
+//     }
+// }
 
 // Traits as parameters
 // this function only accept items which have implemented Summary trait
-pub fn noftiy(item: &impl Summary) {
-    println!("{}", item.summarize());
-}
+// pub fn noftiy(item: &impl Summary) {
+//     println!("{}", item.summarize());
+// }
+// traits::notifier::notify is the same idea, generic over the subscriber it notifies
 
 // function generic types
 // <T: std::cmp::PartialOrd> tells the compiler that T can only be things which can are comparable not everything
@@ -0,0 +1,19 @@
+use std::panic::{self, AssertUnwindSafe};
+
+// Helpers for the it_* checks that are shared between modules
+
+// Runs f with the default panic message switched off and returns the panic message, if any.
+// The panics in the checks that use it are expected, they shouldn't clutter the output.
+pub fn catch_quiet_panic<F: FnOnce()>(f: F) -> Option<String> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(hook);
+    let payload = result.err()?;
+    match payload.downcast::<String>() {
+        Ok(message) => Some(*message),
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string()),
+    }
+}
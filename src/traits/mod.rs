@@ -0,0 +1,2 @@
+pub mod notifier;
+pub mod summary;
@@ -0,0 +1,365 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::advance_rust::messengers::{ChannelMessenger, WriteMessenger};
+use crate::advance_rust::mock::{Mock, MockMethod};
+use crate::advance_rust::smart_pointers::{Messenger, MockMessenger};
+use crate::traits::summary::{Post, Summary};
+
+// Notifier
+// noftiy(&impl Summary) could only print. The Notifier sends the summary of anything implementing
+// Summary to everyone subscribed to a topic. A subscriber is anything implementing Notify, and it
+// decides how low a priority it still wants to hear about. MessengerSubscriber delivers through any
+// Messenger (stdout, a file, a channel, SMTP), so one publish can fan out to several sinks.
+//
+// Static and dynamic dispatch
+// Notifier<N> keeps its subscribers in a Vec<N>:
+//  Notifier<MessengerSubscriber<ChannelMessenger>> holds one kind of subscriber, every call to notify is
+//  known at compile time and can be inlined (static dispatch, like `item: &impl Summary`)
+//  Notifier (the default, Notifier<Box<dyn Notify>>) holds any mix of subscribers, every call goes
+//  through the vtable (dynamic dispatch, like the Feed's Vec<Box<dyn Summary>>)
+// Box<dyn Notify> implements Notify itself, so the same Notifier code serves both.
+
+pub fn notifier() {
+    let mut notifier = Notifier::new();
+    notifier.subscribe_boxed(
+        "releases",
+        MessengerSubscriber::new(WriteMessenger::stdout(), Priority::Low),
+    );
+    notifier.publish(
+        "releases",
+        Priority::High,
+        &Post::new("rustlang", "Rust 1.78 is out", 1_714_608_000),
+    );
+
+    it_delivers_to_every_subscriber_of_a_topic();
+    it_filters_by_priority();
+    it_dispatches_statically_and_dynamically();
+    it_fans_out_through_messengers();
+}
+
+// Ordered, so `priority >= subscriber.min_priority()` reads naturally
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    Normal,
+    High,
+    Urgent,
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+            Priority::Urgent => "URGENT",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// What a subscriber gets, the item is summarized once no matter how many subscribers there are
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub topic: String,
+    pub priority: Priority,
+    pub author: String,
+    pub summary: String,
+}
+
+impl Notification {
+    pub fn new(topic: &str, priority: Priority, item: &(impl Summary + ?Sized)) -> Notification {
+        Notification {
+            topic: String::from(topic),
+            priority,
+            author: item.summarize_author(),
+            summary: item.summarize(),
+        }
+    }
+}
+
+// [topic] priority: summary
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.topic, self.priority, self.summary)
+    }
+}
+
+pub trait Notify {
+    fn notify(&self, notification: &Notification);
+
+    // Notifications below this priority are not delivered
+    fn min_priority(&self) -> Priority {
+        Priority::Low
+    }
+}
+
+impl<N: Notify + ?Sized> Notify for Box<N> {
+    fn notify(&self, notification: &Notification) {
+        (**self).notify(notification)
+    }
+
+    fn min_priority(&self) -> Priority {
+        (**self).min_priority()
+    }
+}
+
+// Delivers notifications as messages, `[topic] priority: summary`
+pub struct MessengerSubscriber<M: Messenger> {
+    messenger: M,
+    min_priority: Priority,
+}
+
+impl<M: Messenger> MessengerSubscriber<M> {
+    pub fn new(messenger: M, min_priority: Priority) -> MessengerSubscriber<M> {
+        MessengerSubscriber {
+            messenger,
+            min_priority,
+        }
+    }
+
+    pub fn messenger(&self) -> &M {
+        &self.messenger
+    }
+}
+
+impl<M: Messenger> Notify for MessengerSubscriber<M> {
+    fn notify(&self, notification: &Notification) {
+        self.messenger.send(&notification.to_string());
+    }
+
+    fn min_priority(&self) -> Priority {
+        self.min_priority
+    }
+}
+
+// Sends the notification to one subscriber if its priority is high enough, returns whether it did.
+// Generic over both, so for concrete types it compiles to direct calls.
+pub fn notify<N: Notify + ?Sized>(subscriber: &N, notification: &Notification) -> bool {
+    if notification.priority < subscriber.min_priority() {
+        return false;
+    }
+    subscriber.notify(notification);
+    true
+}
+
+pub struct Notifier<N: Notify = Box<dyn Notify>> {
+    topics: HashMap<String, Vec<N>>,
+}
+
+impl<N: Notify> Notifier<N> {
+    pub fn new() -> Notifier<N> {
+        Notifier {
+            topics: HashMap::new(),
+        }
+    }
+
+    pub fn subscribe(&mut self, topic: &str, subscriber: N) {
+        self.topics
+            .entry(String::from(topic))
+            .or_default()
+            .push(subscriber);
+    }
+
+    pub fn subscribers(&self, topic: &str) -> &[N] {
+        self.topics
+            .get(topic)
+            .map_or(&[], |subscribers| subscribers)
+    }
+
+    // Sorted, so the output doesn't depend on HashMap order
+    pub fn topics(&self) -> Vec<&str> {
+        let mut topics: Vec<&str> = self.topics.keys().map(|topic| topic.as_str()).collect();
+        topics.sort();
+        topics
+    }
+
+    // Summarizes item and notifies every subscriber of topic that wants this priority, in the
+    // order they subscribed. Returns how many were notified.
+    pub fn publish(
+        &self,
+        topic: &str,
+        priority: Priority,
+        item: &(impl Summary + ?Sized),
+    ) -> usize {
+        let notification = Notification::new(topic, priority, item);
+        self.subscribers(topic)
+            .iter()
+            .filter(|subscriber| notify(*subscriber, &notification))
+            .count()
+    }
+}
+
+impl Notifier<Box<dyn Notify>> {
+    // Boxes the subscriber, so subscribers of different types can share a topic
+    pub fn subscribe_boxed(&mut self, topic: &str, subscriber: impl Notify + 'static) {
+        self.subscribe(topic, Box::new(subscriber));
+    }
+}
+
+impl<N: Notify> Default for Notifier<N> {
+    fn default() -> Notifier<N> {
+        Notifier::new()
+    }
+}
+
+// A subscriber recording its notifications in a mock, so tests can check what arrived in which order
+struct NotifyMock {
+    notify: MockMethod<String, ()>,
+    min_priority: Priority,
+}
+
+impl NotifyMock {
+    fn new(mock: &Mock, name: &'static str, min_priority: Priority) -> NotifyMock {
+        NotifyMock {
+            notify: mock.method(name),
+            min_priority,
+        }
+    }
+}
+
+impl Notify for NotifyMock {
+    fn notify(&self, notification: &Notification) {
+        self.notify.call(notification.to_string())
+    }
+
+    fn min_priority(&self) -> Priority {
+        self.min_priority
+    }
+}
+
+// A writer the test keeps a clone of, so it can read what a boxed WriteMessenger wrote
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn it_delivers_to_every_subscriber_of_a_topic() {
+    let mock = Mock::new();
+    let mut notifier = Notifier::new();
+    notifier.subscribe_boxed("news", NotifyMock::new(&mock, "alice", Priority::Low));
+    notifier.subscribe_boxed("news", NotifyMock::new(&mock, "bob", Priority::Low));
+    notifier.subscribe_boxed("sports", NotifyMock::new(&mock, "carol", Priority::Low));
+
+    let post = Post::new("kartik", "hello", 0);
+    assert_eq!(notifier.publish("news", Priority::Normal, &post), 2);
+    assert_eq!(notifier.publish("weather", Priority::Urgent, &post), 0);
+    assert_eq!(
+        notifier.publish("sports", Priority::Low, &String::from("goal")),
+        1
+    );
+
+    mock.assert_calls(&[
+        r#"alice("[news] normal: (Read more from @kartik...)")"#,
+        r#"bob("[news] normal: (Read more from @kartik...)")"#,
+        r#"carol("[sports] low: anonymous: goal")"#,
+    ]);
+    assert_eq!(notifier.topics(), ["news", "sports"]);
+    assert_eq!(notifier.subscribers("news").len(), 2);
+    assert!(notifier.subscribers("weather").is_empty());
+}
+
+fn it_filters_by_priority() {
+    let mock = Mock::new();
+    let mut notifier = Notifier::new();
+    let pager = NotifyMock::new(&mock, "pager", Priority::Urgent);
+    notifier.subscribe_boxed("alerts", pager);
+    notifier.subscribe_boxed("alerts", NotifyMock::new(&mock, "inbox", Priority::Normal));
+
+    let alert = String::from("disk full");
+    assert_eq!(notifier.publish("alerts", Priority::Low, &alert), 0);
+    assert_eq!(notifier.publish("alerts", Priority::Normal, &alert), 1);
+    assert_eq!(notifier.publish("alerts", Priority::Urgent, &alert), 2);
+
+    mock.assert_calls(&[
+        r#"inbox("[alerts] normal: anonymous: disk full")"#,
+        r#"pager("[alerts] URGENT: anonymous: disk full")"#,
+        r#"inbox("[alerts] URGENT: anonymous: disk full")"#,
+    ]);
+    assert!(Priority::Low < Priority::Normal && Priority::High < Priority::Urgent);
+}
+
+fn it_dispatches_statically_and_dynamically() {
+    // one concrete subscriber type: no boxes, no vtable
+    let mut notifier: Notifier<MessengerSubscriber<MockMessenger>> = Notifier::default();
+    notifier.subscribe(
+        "team",
+        MessengerSubscriber::new(MockMessenger::new(), Priority::Low),
+    );
+    notifier.subscribe(
+        "team",
+        MessengerSubscriber::new(MockMessenger::new(), Priority::High),
+    );
+    let post = Post::new("kartik", "standup moved", 0);
+    assert_eq!(notifier.publish("team", Priority::Normal, &post), 1);
+    let sent: Vec<usize> = notifier
+        .subscribers("team")
+        .iter()
+        .map(|subscriber| subscriber.messenger().sent_messages.borrow().len())
+        .collect();
+    assert_eq!(sent, [1, 0]);
+
+    // notify on its own works on concrete types and trait objects alike
+    let notification = Notification::new("team", Priority::High, &post);
+    let concrete = MessengerSubscriber::new(MockMessenger::new(), Priority::High);
+    let boxed: Box<dyn Notify> = Box::new(MessengerSubscriber::new(
+        MockMessenger::new(),
+        Priority::Urgent,
+    ));
+    assert!(notify(&concrete, &notification));
+    assert!(!notify(&*boxed, &notification));
+    assert!(!notify(&boxed, &notification));
+    assert_eq!(
+        *concrete.messenger().sent_messages.borrow(),
+        ["[team] high: (Read more from @kartik...)"]
+    );
+    assert_eq!(notification.author, "@kartik");
+}
+
+fn it_fans_out_through_messengers() {
+    // two different Messengers behind one topic
+    let (channel, received) = ChannelMessenger::new();
+    let written = SharedBuffer::default();
+    let mut notifier = Notifier::new();
+    notifier.subscribe_boxed("deploys", MessengerSubscriber::new(channel, Priority::Low));
+    notifier.subscribe_boxed(
+        "deploys",
+        MessengerSubscriber::new(WriteMessenger::new(written.clone()), Priority::High),
+    );
+
+    let deploy = Post::new("ci", "deployed v2", 0);
+    assert_eq!(notifier.publish("deploys", Priority::Normal, &deploy), 1);
+    assert_eq!(notifier.publish("deploys", Priority::High, &deploy), 2);
+    let messages: Vec<String> = received.try_iter().collect();
+    assert_eq!(
+        messages,
+        [
+            "[deploys] normal: (Read more from @ci...)",
+            "[deploys] high: (Read more from @ci...)"
+        ]
+    );
+    // the write sink only wants High and up
+    assert_eq!(
+        written.contents(),
+        "[deploys] high: (Read more from @ci...)\n"
+    );
+}
@@ -0,0 +1,265 @@
+use std::cmp::Reverse;
+use std::fmt::Write;
+
+// Summary
+// A trait can mix required methods, which every implementor has to write, with default methods built on
+// top of them. Summary requires summarize_author, and its default summarize calls it, so implementing one
+// small method gets an implementor the whole trait. A type can still override summarize when it has
+// something better to say, Article does.
+//
+// Trait objects
+// Post and Article are different types, so they can't go into one Vec<T>. Box<dyn Summary> is a pointer
+// to any value that implements Summary together with a table of its methods (a vtable), so the Feed can
+// keep posts, articles and anything else that implements Summary in one Vec<Box<dyn Summary>> and call
+// summarize on each without knowing what it is. The method to run is looked up at runtime (dynamic
+// dispatch), where `item: &impl Summary` is compiled into a separate function per type (static dispatch).
+
+pub trait Summary {
+    fn summarize_author(&self) -> String;
+
+    fn summarize(&self) -> String {
+        format!("(Read more from {}...)", self.summarize_author())
+    }
+
+    // Seconds since the unix epoch at which the content was published. None for things that aren't
+    // published content, the feed shows those last.
+    fn timestamp(&self) -> Option<u64> {
+        None
+    }
+}
+
+// A short post, uses the default summarize
+pub struct Post {
+    pub username: String,
+    pub content: String,
+    pub published_at: u64,
+}
+
+impl Post {
+    pub fn new(username: &str, content: &str, published_at: u64) -> Post {
+        Post {
+            username: String::from(username),
+            content: String::from(content),
+            published_at,
+        }
+    }
+}
+
+impl Summary for Post {
+    fn summarize_author(&self) -> String {
+        format!("@{}", self.username)
+    }
+
+    fn timestamp(&self) -> Option<u64> {
+        Some(self.published_at)
+    }
+}
+
+pub struct Article {
+    pub headline: String,
+    pub author: String,
+    pub location: String,
+    pub body: String,
+    pub published_at: u64,
+}
+
+impl Summary for Article {
+    fn summarize_author(&self) -> String {
+        self.author.clone()
+    }
+
+    fn summarize(&self) -> String {
+        format!(
+            "{}, by {} ({})",
+            self.headline,
+            self.summarize_author(),
+            self.location
+        )
+    }
+
+    fn timestamp(&self) -> Option<u64> {
+        Some(self.published_at)
+    }
+}
+
+// Plain text, the author isn't known
+impl Summary for String {
+    fn summarize_author(&self) -> String {
+        String::from("anonymous")
+    }
+
+    fn summarize(&self) -> String {
+        format!("{}: {}", self.summarize_author(), self)
+    }
+}
+
+// Anything that implements Summary, shown newest first
+pub struct Feed {
+    items: Vec<Box<dyn Summary>>,
+}
+
+impl Feed {
+    pub fn new() -> Feed {
+        Feed { items: Vec::new() }
+    }
+
+    // 'static because the feed keeps the item, it can't hold on to borrowed data
+    pub fn push(&mut self, item: impl Summary + 'static) {
+        self.items.push(Box::new(item));
+    }
+
+    pub fn push_boxed(&mut self, item: Box<dyn Summary>) {
+        self.items.push(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // Newest first, items without a timestamp at the end. The sort is stable, so items with the
+    // same timestamp stay in the order they were pushed.
+    pub fn timeline(&self) -> Vec<&dyn Summary> {
+        let mut items: Vec<&dyn Summary> = self.items.iter().map(|item| item.as_ref()).collect();
+        items.sort_by_key(|item| {
+            let timestamp = item.timestamp();
+            (timestamp.is_none(), Reverse(timestamp))
+        });
+        items
+    }
+
+    // One line per item: `2024-05-01 09:30 UTC  summary`
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for item in self.timeline() {
+            let when = match item.timestamp() {
+                Some(timestamp) => format_timestamp(timestamp),
+                None => String::from("-"),
+            };
+            writeln!(out, "{:<20}  {}", when, item.summarize()).unwrap();
+        }
+        out
+    }
+}
+
+impl Default for Feed {
+    fn default() -> Feed {
+        Feed::new()
+    }
+}
+
+// `YYYY-MM-DD HH:MM UTC` without a date crate: the days since the epoch are turned into a date with
+// the days-to-civil algorithm (http://howardhinnant.github.io/date_algorithms.html), which counts
+// in 400 year eras starting on March 1st so leap days fall at the end of a year
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = timestamp / 86_400;
+    let seconds = timestamp % 86_400;
+
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60
+    )
+}
+
+pub fn summary() {
+    let mut feed = Feed::new();
+    feed.push(Post::new(
+        "kartik",
+        "learning about trait objects",
+        1_714_555_800,
+    ));
+    feed.push(Article {
+        headline: String::from("Rust 1.78 released"),
+        author: String::from("The Rust Release Team"),
+        location: String::from("the internet"),
+        body: String::from("..."),
+        published_at: 1_714_608_000,
+    });
+    print!("{}", feed.render());
+
+    it_builds_summarize_from_summarize_author();
+    it_sorts_the_feed_newest_first();
+    it_formats_timestamps();
+}
+
+fn it_builds_summarize_from_summarize_author() {
+    let post = Post::new("kartik", "hello", 0);
+    assert_eq!(post.summarize_author(), "@kartik");
+    // the default summary doesn't show the content
+    assert_eq!(post.summarize(), "(Read more from @kartik...)");
+    assert!(!post.summarize().contains(&post.content));
+
+    let article = Article {
+        headline: String::from("Penguins win the Stanley Cup Championship!"),
+        author: String::from("Iceburgh"),
+        location: String::from("Pittsburgh, PA, USA"),
+        body: String::from(
+            "The Pittsburgh Penguins once again are the best hockey team in the NHL.",
+        ),
+        published_at: 0,
+    };
+    assert_eq!(
+        article.summarize(),
+        "Penguins win the Stanley Cup Championship!, by Iceburgh (Pittsburgh, PA, USA)"
+    );
+    assert!(!article.body.is_empty());
+
+    let text = String::from("just some text");
+    assert_eq!(text.summarize(), "anonymous: just some text");
+    assert_eq!(text.timestamp(), None);
+}
+
+fn it_sorts_the_feed_newest_first() {
+    let mut feed = Feed::default();
+    assert!(feed.is_empty());
+    feed.push(Post::new("old", "first", 100));
+    feed.push(String::from("no timestamp"));
+    feed.push(Post::new("new", "third", 300));
+    feed.push_boxed(Box::new(Post::new("tied", "same time as new", 300)));
+    feed.push(Post::new("middle", "second", 200));
+    assert_eq!(feed.len(), 5);
+
+    let authors: Vec<String> = feed
+        .timeline()
+        .iter()
+        .map(|item| item.summarize_author())
+        .collect();
+    assert_eq!(authors, ["@new", "@tied", "@middle", "@old", "anonymous"]);
+
+    let mut feed = Feed::new();
+    feed.push(Post::new("kartik", "hello", 1_714_555_800));
+    feed.push(String::from("hi"));
+    assert_eq!(
+        feed.render(),
+        "2024-05-01 09:30 UTC  (Read more from @kartik...)\n-                     anonymous: hi\n"
+    );
+}
+
+fn it_formats_timestamps() {
+    assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+    assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00 UTC");
+    assert_eq!(format_timestamp(1_709_251_199), "2024-02-29 23:59 UTC");
+    assert_eq!(format_timestamp(1_735_689_600), "2025-01-01 00:00 UTC");
+}
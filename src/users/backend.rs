@@ -0,0 +1,355 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::users::json;
+use crate::users::store::{UserRecord, UserStore, UserStoreError};
+
+// Storage backends
+// UserStore only talks to its storage through the UserBackend trait, so the same store can keep its
+// users in memory, in a JSON file or in an append-only log, and a test can hand it a mock instead.
+
+// The methods take &self like Messenger::send does. Backends that need to change state behind a shared
+// reference (InMemoryBackend, MockBackend) use RefCell for it, the file based ones simply touch the disk.
+pub trait UserBackend {
+    // Every user currently stored
+    fn load(&self) -> Result<Vec<UserRecord>, UserStoreError>;
+    // Insert or replace the user with the same username
+    fn put(&self, user: &UserRecord) -> Result<(), UserStoreError>;
+    fn remove(&self, username: &str) -> Result<(), UserStoreError>;
+}
+
+// Lets UserStore<Box<dyn UserBackend>> pick the backend at runtime
+impl<B: UserBackend + ?Sized> UserBackend for Box<B> {
+    fn load(&self) -> Result<Vec<UserRecord>, UserStoreError> {
+        (**self).load()
+    }
+
+    fn put(&self, user: &UserRecord) -> Result<(), UserStoreError> {
+        (**self).put(user)
+    }
+
+    fn remove(&self, username: &str) -> Result<(), UserStoreError> {
+        (**self).remove(username)
+    }
+}
+
+// Lets a test keep the backend and look at it after the store is done with it
+impl<B: UserBackend + ?Sized> UserBackend for &B {
+    fn load(&self) -> Result<Vec<UserRecord>, UserStoreError> {
+        (**self).load()
+    }
+
+    fn put(&self, user: &UserRecord) -> Result<(), UserStoreError> {
+        (**self).put(user)
+    }
+
+    fn remove(&self, username: &str) -> Result<(), UserStoreError> {
+        (**self).remove(username)
+    }
+}
+
+// Keeps everything in a HashMap, nothing survives the process
+#[derive(Default)]
+pub struct InMemoryBackend {
+    users: RefCell<HashMap<String, UserRecord>>,
+}
+
+impl InMemoryBackend {
+    pub fn new() -> InMemoryBackend {
+        InMemoryBackend::default()
+    }
+}
+
+impl UserBackend for InMemoryBackend {
+    fn load(&self) -> Result<Vec<UserRecord>, UserStoreError> {
+        Ok(self.users.borrow().values().cloned().collect())
+    }
+
+    fn put(&self, user: &UserRecord) -> Result<(), UserStoreError> {
+        self.users
+            .borrow_mut()
+            .insert(user.username.clone(), user.clone());
+        Ok(())
+    }
+
+    fn remove(&self, username: &str) -> Result<(), UserStoreError> {
+        self.users.borrow_mut().remove(username);
+        Ok(())
+    }
+}
+
+// The whole registry as one JSON document, rewritten with write-rename on every change
+pub struct JsonFileBackend {
+    path: PathBuf,
+}
+
+impl JsonFileBackend {
+    pub fn new(path: impl AsRef<Path>) -> JsonFileBackend {
+        JsonFileBackend {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn write(&self, mut users: Vec<UserRecord>) -> Result<(), UserStoreError> {
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        write_atomically(&self.path, &json::encode_users(&users))?;
+        Ok(())
+    }
+}
+
+impl UserBackend for JsonFileBackend {
+    // A missing file is treated as an empty registry
+    fn load(&self) -> Result<Vec<UserRecord>, UserStoreError> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => json::decode_users(&content),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn put(&self, user: &UserRecord) -> Result<(), UserStoreError> {
+        let mut users = self.load()?;
+        users.retain(|u| u.username != user.username);
+        users.push(user.clone());
+        self.write(users)
+    }
+
+    fn remove(&self, username: &str) -> Result<(), UserStoreError> {
+        let mut users = self.load()?;
+        users.retain(|u| u.username != username);
+        self.write(users)
+    }
+}
+
+fn write_atomically(path: &Path, content: &str) -> io::Result<()> {
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+// Append-only log
+// Every change is appended as one line and the file is never rewritten in place:
+// put {"username": "Kartik", "email": "kartik@example.com", "age": 22, "active": true}
+// remove "Kartik"
+// Loading replays the lines from the top. A crash can at worst leave a half written last line,
+// which is ignored, so every change that was fully written is kept.
+pub struct LogFileBackend {
+    path: PathBuf,
+}
+
+impl LogFileBackend {
+    pub fn new(path: impl AsRef<Path>) -> LogFileBackend {
+        LogFileBackend {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    fn append(&self, line: &str) -> Result<(), UserStoreError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+
+        // cut off a torn line left behind by a crash, otherwise the new line would be glued to it
+        let len = file.metadata()?.len();
+        if len > 0 {
+            let mut last = [0u8; 1];
+            file.seek(SeekFrom::Start(len - 1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                let content = fs::read(&self.path)?;
+                let keep = content.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+                file.set_len(keep as u64)?;
+            }
+        }
+
+        file.write_all(format!("{}\n", line).as_bytes())?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    // The log grows with every change, compact replaces it with one put per live user
+    pub fn compact(&self) -> Result<(), UserStoreError> {
+        let mut users = self.load()?;
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        let mut content = String::new();
+        for user in &users {
+            content.push_str(&format!("put {}\n", json::encode_user(user)));
+        }
+        write_atomically(&self.path, &content)?;
+        Ok(())
+    }
+}
+
+impl UserBackend for LogFileBackend {
+    fn load(&self) -> Result<Vec<UserRecord>, UserStoreError> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut users: HashMap<String, UserRecord> = HashMap::new();
+        let complete = match content.rfind('\n') {
+            Some(index) => &content[..index],
+            None => "",
+        };
+        for (number, line) in complete.lines().enumerate() {
+            let parsed = if let Some(rest) = line.strip_prefix("put ") {
+                json::decode_user(rest).map(|user| {
+                    users.insert(user.username.clone(), user);
+                })
+            } else if let Some(rest) = line.strip_prefix("remove ") {
+                json::decode_string(rest).map(|username| {
+                    users.remove(&username);
+                })
+            } else {
+                Err(UserStoreError::Parse(String::from("unknown operation")))
+            };
+
+            if let Err(err) = parsed {
+                return Err(UserStoreError::Parse(format!("line {}: {}", number + 1, err)));
+            }
+        }
+        Ok(users.into_values().collect())
+    }
+
+    fn put(&self, user: &UserRecord) -> Result<(), UserStoreError> {
+        self.append(&format!("put {}", json::encode_user(user)))
+    }
+
+    fn remove(&self, username: &str) -> Result<(), UserStoreError> {
+        self.append(&format!("remove {}", json::quote(username)))
+    }
+}
+
+// Test double in the spirit of MockMessenger: it serves a fixed set of users from load
+// and records every write so a test can assert exactly what the store persisted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendWrite {
+    Put(UserRecord),
+    Remove(String),
+}
+
+pub struct MockBackend {
+    initial: Vec<UserRecord>,
+    pub writes: RefCell<Vec<BackendWrite>>,
+}
+
+impl MockBackend {
+    pub fn with_users(users: Vec<UserRecord>) -> MockBackend {
+        MockBackend {
+            initial: users,
+            writes: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl UserBackend for MockBackend {
+    fn load(&self) -> Result<Vec<UserRecord>, UserStoreError> {
+        Ok(self.initial.clone())
+    }
+
+    fn put(&self, user: &UserRecord) -> Result<(), UserStoreError> {
+        self.writes.borrow_mut().push(BackendWrite::Put(user.clone()));
+        Ok(())
+    }
+
+    fn remove(&self, username: &str) -> Result<(), UserStoreError> {
+        self.writes
+            .borrow_mut()
+            .push(BackendWrite::Remove(String::from(username)));
+        Ok(())
+    }
+}
+
+pub fn user_backends() {
+    it_records_exactly_the_writes_a_store_makes();
+    it_keeps_users_in_memory();
+
+    let dir = std::env::temp_dir();
+    let json_path = dir.join(format!("rust-notes-backend-{}.json", std::process::id()));
+    let log_path = dir.join(format!("rust-notes-backend-{}.log", std::process::id()));
+
+    it_behaves_the_same_on_every_backend(Box::new(JsonFileBackend::new(&json_path)));
+    it_behaves_the_same_on_every_backend(Box::new(LogFileBackend::new(&log_path)));
+    it_ignores_a_torn_last_log_line(&log_path);
+
+    let _ = fs::remove_file(&json_path);
+    let _ = fs::remove_file(&log_path);
+}
+
+fn it_records_exactly_the_writes_a_store_makes() {
+    let backend = MockBackend::with_users(vec![UserRecord::new("Ashvin", "ashvin@example.com", 23)]);
+    let mut store = UserStore::with_backend(&backend).unwrap();
+
+    store.add(UserRecord::new("Kartik", "kartik@example.com", 22)).unwrap();
+    store.deactivate("Ashvin").unwrap();
+    store.delete("Kartik").unwrap();
+    // failed operations must not touch the backend
+    assert!(store.delete("Nobody").is_err());
+
+    let mut ashvin = UserRecord::new("Ashvin", "ashvin@example.com", 23);
+    ashvin.active = false;
+    assert_eq!(
+        *backend.writes.borrow(),
+        vec![
+            BackendWrite::Put(UserRecord::new("Kartik", "kartik@example.com", 22)),
+            BackendWrite::Put(ashvin),
+            BackendWrite::Remove(String::from("Kartik")),
+        ]
+    );
+}
+
+fn it_keeps_users_in_memory() {
+    let backend = InMemoryBackend::new();
+    {
+        let mut store = UserStore::with_backend(&backend).unwrap();
+        store.add(UserRecord::new("Kartik", "kartik@example.com", 22)).unwrap();
+    }
+    let store = UserStore::with_backend(&backend).unwrap();
+    assert_eq!(store.list().len(), 1);
+}
+
+fn it_behaves_the_same_on_every_backend(backend: Box<dyn UserBackend>) {
+    {
+        let mut store = UserStore::with_backend(&*backend).unwrap();
+        store.add(UserRecord::new("Kartik", "kartik@example.com", 22)).unwrap();
+        store.add(UserRecord::new("Ashvin", "ashvin@example.com", 23)).unwrap();
+        store.deactivate("Kartik").unwrap();
+        store.delete("Ashvin").unwrap();
+    }
+
+    let store = UserStore::with_backend(&*backend).unwrap();
+    let users = store.list();
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].username, "Kartik");
+    assert!(!users[0].active);
+}
+
+fn it_ignores_a_torn_last_log_line(path: &Path) {
+    let mut file = OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(b"put {\"username\": \"Half").unwrap();
+
+    let backend = LogFileBackend::new(path);
+    assert_eq!(backend.load().unwrap().len(), 1);
+
+    // the next append replaces the torn line instead of gluing onto it
+    backend.put(&UserRecord::new("Ashvin", "ashvin@example.com", 23)).unwrap();
+    assert_eq!(backend.load().unwrap().len(), 2);
+    backend.remove("Ashvin").unwrap();
+
+    backend.compact().unwrap();
+    let content = fs::read_to_string(path).unwrap();
+    assert_eq!(content.lines().count(), 1);
+    assert!(content.starts_with("put {\"username\": \"Kartik\""));
+}
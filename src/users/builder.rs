@@ -0,0 +1,472 @@
+use std::fmt;
+
+use crate::users::store::UserRecord;
+
+// Validated users
+// A struct literal like User { username, email, .. } accepts anything, an empty username or "not an email"
+// is just as valid to the compiler as a real one. The builder below only turns raw input into a UserRecord
+// when every rule holds, and UserStore runs the same checks on anything that is added to it.
+
+// Username rules: 3 to 20 characters, ASCII letters, digits and '_', starting with a letter.
+// Email rules: one '@', a non-empty local part, a domain made of at least two dot separated labels
+// of letters, digits and '-', and no whitespace or control characters anywhere. '"' and '\' are only
+// allowed in a quoted local part like "john\"doe"@example.com, where every '"' and '\' inside the
+// quotes has to be escaped with a '\'.
+
+pub const USERNAME_MIN_LEN: usize = 3;
+pub const USERNAME_MAX_LEN: usize = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    MissingUsername,
+    UsernameTooShort { len: usize },
+    UsernameTooLong { len: usize },
+    UsernameInvalidChar(char),
+    UsernameMustStartWithLetter,
+    MissingEmail,
+    EmailContainsWhitespace,
+    EmailMissingAt,
+    EmailMultipleAt,
+    EmailEmptyLocalPart,
+    EmailInvalidChar(char),
+    EmailInvalidDomain(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::MissingUsername => write!(f, "username is required"),
+            ValidationError::UsernameTooShort { len } => write!(
+                f,
+                "username has {} characters, at least {} are required",
+                len, USERNAME_MIN_LEN
+            ),
+            ValidationError::UsernameTooLong { len } => write!(
+                f,
+                "username has {} characters, at most {} are allowed",
+                len, USERNAME_MAX_LEN
+            ),
+            ValidationError::UsernameInvalidChar(c) => {
+                write!(
+                    f,
+                    "username contains {:?}, only letters, digits and '_' are allowed",
+                    c
+                )
+            }
+            ValidationError::UsernameMustStartWithLetter => {
+                write!(f, "username must start with a letter")
+            }
+            ValidationError::MissingEmail => write!(f, "email is required"),
+            ValidationError::EmailContainsWhitespace => write!(f, "email contains whitespace"),
+            ValidationError::EmailMissingAt => write!(f, "email has no '@'"),
+            ValidationError::EmailMultipleAt => write!(f, "email has more than one '@'"),
+            ValidationError::EmailEmptyLocalPart => write!(f, "email has nothing before the '@'"),
+            ValidationError::EmailInvalidChar(c) => {
+                write!(f, "email contains {:?} where it isn't allowed", c)
+            }
+            ValidationError::EmailInvalidDomain(domain) => {
+                write!(f, "email domain {:?} is not a valid domain", domain)
+            }
+        }
+    }
+}
+
+// Every rule that failed, not just the first one, so a form can show all problems at once
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid user:")?;
+        for err in &self.0 {
+            write!(f, "\n  - {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+pub fn validate_username(username: &str) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    let len = username.chars().count();
+    if len < USERNAME_MIN_LEN {
+        errors.push(ValidationError::UsernameTooShort { len });
+    }
+    if len > USERNAME_MAX_LEN {
+        errors.push(ValidationError::UsernameTooLong { len });
+    }
+    if let Some(first) = username.chars().next() {
+        if !first.is_ascii_alphabetic() {
+            errors.push(ValidationError::UsernameMustStartWithLetter);
+        }
+    }
+    // report each bad character once
+    let mut seen = Vec::new();
+    for c in username.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || seen.contains(&c) {
+            continue;
+        }
+        seen.push(c);
+        errors.push(ValidationError::UsernameInvalidChar(c));
+    }
+    errors
+}
+
+pub fn validate_email(email: &str) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+    if email.chars().any(char::is_whitespace) {
+        errors.push(ValidationError::EmailContainsWhitespace);
+    }
+
+    let parts: Vec<&str> = email.split('@').collect();
+    match parts.len() {
+        1 => errors.push(ValidationError::EmailMissingAt),
+        2 => {
+            let (local, domain) = (parts[0], parts[1]);
+            if local.is_empty() {
+                errors.push(ValidationError::EmailEmptyLocalPart);
+            }
+            // report each bad character once
+            let mut seen = Vec::new();
+            for c in invalid_local_chars(local) {
+                if !seen.contains(&c) {
+                    seen.push(c);
+                    errors.push(ValidationError::EmailInvalidChar(c));
+                }
+            }
+            if !is_valid_domain(domain) {
+                errors.push(ValidationError::EmailInvalidDomain(String::from(domain)));
+            }
+        }
+        _ => errors.push(ValidationError::EmailMultipleAt),
+    }
+    errors
+}
+
+// The characters of a local part that break the rules: control characters (whitespace ones are
+// already reported as whitespace), '"' and '\' outside of quotes, and unescaped ones inside
+fn invalid_local_chars(local: &str) -> Vec<char> {
+    let mut invalid: Vec<char> = local
+        .chars()
+        .filter(|c| c.is_control() && !c.is_whitespace())
+        .collect();
+    let quoted = local.len() >= 2 && local.starts_with('"') && local.ends_with('"');
+    if !quoted {
+        invalid.extend(local.chars().filter(|c| *c == '"' || *c == '\\'));
+        return invalid;
+    }
+    let mut chars = local[1..local.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            // skips the escaped character, a '\' right before the closing quote would escape it
+            '\\' if chars.next().is_none() => invalid.push('\\'),
+            '"' => invalid.push('"'),
+            _ => {}
+        }
+    }
+    invalid
+}
+
+fn is_valid_domain(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+// Runtime checked builder
+// Every field is optional while building, build() checks that the required ones are present
+// and valid and reports everything that is wrong in one go.
+#[derive(Debug, Default)]
+pub struct UserBuilder {
+    username: Option<String>,
+    email: Option<String>,
+    age: u32,
+    active: bool,
+}
+
+impl UserRecord {
+    pub fn builder() -> UserBuilder {
+        UserBuilder::new()
+    }
+}
+
+impl UserBuilder {
+    pub fn new() -> UserBuilder {
+        UserBuilder {
+            active: true,
+            ..UserBuilder::default()
+        }
+    }
+
+    pub fn username(mut self, username: &str) -> UserBuilder {
+        self.username = Some(String::from(username));
+        self
+    }
+
+    pub fn email(mut self, email: &str) -> UserBuilder {
+        self.email = Some(String::from(email));
+        self
+    }
+
+    pub fn age(mut self, age: u32) -> UserBuilder {
+        self.age = age;
+        self
+    }
+
+    pub fn active(mut self, active: bool) -> UserBuilder {
+        self.active = active;
+        self
+    }
+
+    pub fn build(self) -> Result<UserRecord, ValidationErrors> {
+        let mut errors = Vec::new();
+        match &self.username {
+            Some(username) => errors.extend(validate_username(username)),
+            None => errors.push(ValidationError::MissingUsername),
+        }
+        match &self.email {
+            Some(email) => errors.extend(validate_email(email)),
+            None => errors.push(ValidationError::MissingEmail),
+        }
+        if !errors.is_empty() {
+            return Err(ValidationErrors(errors));
+        }
+
+        Ok(UserRecord {
+            username: self.username.unwrap(),
+            email: self.email.unwrap(),
+            age: self.age,
+            active: self.active,
+        })
+    }
+}
+
+// Type-state builder
+// The same builder, but "forgot to set the email" is a compile error instead of a runtime one.
+// The type parameters remember which required fields were set: build() only exists on
+// TypedUserBuilder<HasUsername, HasEmail>, so calling it any earlier does not compile.
+//
+// let user = TypedUserBuilder::new().username("Kartik").build();
+// error[E0599]: no method named `build` found for struct `TypedUserBuilder<HasUsername, NoEmail>`
+//
+// tests/typed_user_builder.rs checks that this really doesn't compile.
+// The values themselves can still be wrong, so build() keeps returning a Result.
+
+pub struct NoUsername;
+pub struct HasUsername(String);
+pub struct NoEmail;
+pub struct HasEmail(String);
+
+pub struct TypedUserBuilder<U, E> {
+    username: U,
+    email: E,
+    age: u32,
+    active: bool,
+}
+
+impl TypedUserBuilder<NoUsername, NoEmail> {
+    pub fn new() -> TypedUserBuilder<NoUsername, NoEmail> {
+        TypedUserBuilder {
+            username: NoUsername,
+            email: NoEmail,
+            age: 0,
+            active: true,
+        }
+    }
+}
+
+impl<U, E> TypedUserBuilder<U, E> {
+    pub fn username(self, username: &str) -> TypedUserBuilder<HasUsername, E> {
+        TypedUserBuilder {
+            username: HasUsername(String::from(username)),
+            email: self.email,
+            age: self.age,
+            active: self.active,
+        }
+    }
+
+    pub fn email(self, email: &str) -> TypedUserBuilder<U, HasEmail> {
+        TypedUserBuilder {
+            username: self.username,
+            email: HasEmail(String::from(email)),
+            age: self.age,
+            active: self.active,
+        }
+    }
+
+    pub fn age(mut self, age: u32) -> TypedUserBuilder<U, E> {
+        self.age = age;
+        self
+    }
+
+    pub fn active(mut self, active: bool) -> TypedUserBuilder<U, E> {
+        self.active = active;
+        self
+    }
+}
+
+impl TypedUserBuilder<HasUsername, HasEmail> {
+    pub fn build(self) -> Result<UserRecord, ValidationErrors> {
+        let HasUsername(username) = self.username;
+        let HasEmail(email) = self.email;
+
+        let mut errors = validate_username(&username);
+        errors.extend(validate_email(&email));
+        if !errors.is_empty() {
+            return Err(ValidationErrors(errors));
+        }
+
+        Ok(UserRecord {
+            username,
+            email,
+            age: self.age,
+            active: self.active,
+        })
+    }
+}
+
+pub fn user_builder() {
+    it_builds_a_valid_user();
+    it_lists_every_failed_rule();
+    it_requires_username_and_email();
+    it_checks_typed_builder_values();
+    it_rejects_unsafe_email_characters();
+}
+
+fn it_builds_a_valid_user() {
+    let user = UserRecord::builder()
+        .username("Kartik2__1")
+        .email("kartikg02013@gmail.com")
+        .age(22)
+        .build()
+        .unwrap();
+    assert_eq!(user.username, "Kartik2__1");
+    assert!(user.active);
+
+    let user = UserRecord::builder()
+        .username("Retired")
+        .email("retired@example.com")
+        .active(false)
+        .build()
+        .unwrap();
+    assert!(!user.active);
+
+    let user = TypedUserBuilder::new()
+        .email("ashvin@mail.example.com")
+        .age(23)
+        .active(false)
+        .username("Ashvin")
+        .build()
+        .unwrap();
+    assert_eq!(user.email, "ashvin@mail.example.com");
+    assert!(!user.active);
+}
+
+fn it_lists_every_failed_rule() {
+    let errors = UserRecord::builder()
+        .username("1a-b-")
+        .email("me@@example")
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        errors.0,
+        vec![
+            ValidationError::UsernameMustStartWithLetter,
+            ValidationError::UsernameInvalidChar('-'),
+            ValidationError::EmailMultipleAt,
+        ]
+    );
+
+    assert_eq!(
+        validate_email("my name@example"),
+        vec![
+            ValidationError::EmailContainsWhitespace,
+            ValidationError::EmailInvalidDomain(String::from("example")),
+        ]
+    );
+    assert_eq!(
+        validate_username("ab"),
+        vec![ValidationError::UsernameTooShort { len: 2 }]
+    );
+    assert_eq!(
+        validate_username("a_very_long_username_indeed"),
+        vec![ValidationError::UsernameTooLong { len: 27 }]
+    );
+
+    let message = errors.to_string();
+    assert!(message.contains("username must start with a letter"));
+    assert!(message.contains("more than one '@'"));
+}
+
+fn it_requires_username_and_email() {
+    let errors = UserRecord::builder().age(30).build().unwrap_err();
+    assert_eq!(
+        errors.0,
+        vec![
+            ValidationError::MissingUsername,
+            ValidationError::MissingEmail
+        ]
+    );
+}
+
+fn it_checks_typed_builder_values() {
+    let errors = TypedUserBuilder::new()
+        .username("ok_name")
+        .email("@example.com")
+        .build()
+        .unwrap_err();
+    assert_eq!(errors.0, vec![ValidationError::EmailEmptyLocalPart]);
+}
+
+fn it_rejects_unsafe_email_characters() {
+    assert_eq!(
+        validate_email("bell\u{1}\u{7f}@example.com"),
+        vec![
+            ValidationError::EmailInvalidChar('\u{1}'),
+            ValidationError::EmailInvalidChar('\u{7f}'),
+        ]
+    );
+    assert_eq!(
+        validate_email("tab\tme@example.com"),
+        vec![ValidationError::EmailContainsWhitespace]
+    );
+    assert_eq!(
+        validate_email("quote\"slash\\@example.com"),
+        vec![
+            ValidationError::EmailInvalidChar('"'),
+            ValidationError::EmailInvalidChar('\\'),
+        ]
+    );
+
+    // quoted local parts can hold them, escaped
+    assert!(validate_email("\"john\\\"doe\"@example.com").is_empty());
+    assert!(validate_email("\"back\\\\slash\"@example.com").is_empty());
+    assert_eq!(
+        validate_email("\"john\"doe\"@example.com"),
+        vec![ValidationError::EmailInvalidChar('"')]
+    );
+    assert_eq!(
+        validate_email("\"dangling\\\"@example.com"),
+        vec![ValidationError::EmailInvalidChar('\\')]
+    );
+    assert_eq!(
+        validate_email("\"nul\u{0}\"@example.com"),
+        vec![ValidationError::EmailInvalidChar('\u{0}')]
+    );
+
+    let errors = UserRecord::builder()
+        .username("Kartik")
+        .email("kar\"tik@example.com")
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        errors.to_string(),
+        "invalid user:\n  - email contains '\"' where it isn't allowed"
+    );
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use crate::users::store::{UserRecord, UserStoreError};
+
+// Users are stored as a JSON array with one object per user:
+// [
+//   {"username": "Kartik", "email": "kartik@example.com", "age": 22, "active": true}
+// ]
+
+pub fn encode_users(users: &[UserRecord]) -> String {
+    let mut out = String::from("[\n");
+    for (index, user) in users.iter().enumerate() {
+        out.push_str("  ");
+        out.push_str(&encode_user(user));
+        if index + 1 < users.len() {
+            out.push(',');
+        }
+        out.push('\n');
+    }
+    out.push_str("]\n");
+    out
+}
+
+pub fn encode_user(user: &UserRecord) -> String {
+    format!(
+        "{{\"username\": {}, \"email\": {}, \"age\": {}, \"active\": {}}}",
+        quote(&user.username),
+        quote(&user.email),
+        user.age,
+        user.active
+    )
+}
+
+pub fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn decode_users(content: &str) -> Result<Vec<UserRecord>, UserStoreError> {
+    let mut parser = Parser::new(content);
+    let users = parser.users()?;
+    parser.finish()?;
+    Ok(users)
+}
+
+pub fn decode_user(content: &str) -> Result<UserRecord, UserStoreError> {
+    let mut parser = Parser::new(content);
+    let user = parser.user()?;
+    parser.finish()?;
+    Ok(user)
+}
+
+// Reverses quote, used for values that are stored as a bare JSON string
+pub fn decode_string(content: &str) -> Result<String, UserStoreError> {
+    let mut parser = Parser::new(content);
+    let s = parser.string()?;
+    parser.finish()?;
+    Ok(s)
+}
+
+enum Value {
+    Str(String),
+    Num(u32),
+    Bool(bool),
+}
+
+// A tiny parser that only understands the shapes written by this module
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(content: &str) -> Parser {
+        Parser {
+            chars: content.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), UserStoreError> {
+        self.skip_whitespace();
+        if self.pos != self.chars.len() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(())
+    }
+
+    fn error(&self, msg: &str) -> UserStoreError {
+        UserStoreError::Parse(format!("{} at offset {}", msg, self.pos))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), UserStoreError> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn users(&mut self) -> Result<Vec<UserRecord>, UserStoreError> {
+        let mut users = Vec::new();
+        self.expect('[')?;
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(users);
+        }
+        loop {
+            users.push(self.user()?);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(users);
+                }
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn user(&mut self) -> Result<UserRecord, UserStoreError> {
+        let mut fields: HashMap<String, Value> = HashMap::new();
+        self.expect('{')?;
+        loop {
+            let key = self.string()?;
+            self.expect(':')?;
+            let value = self.value()?;
+            fields.insert(key, value);
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    break;
+                }
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+
+        let username = match fields.remove("username") {
+            Some(Value::Str(s)) => s,
+            _ => return Err(self.error("missing string field username")),
+        };
+        let email = match fields.remove("email") {
+            Some(Value::Str(s)) => s,
+            _ => return Err(self.error("missing string field email")),
+        };
+        let age = match fields.remove("age") {
+            Some(Value::Num(n)) => n,
+            _ => return Err(self.error("missing number field age")),
+        };
+        let active = match fields.remove("active") {
+            Some(Value::Bool(b)) => b,
+            _ => return Err(self.error("missing bool field active")),
+        };
+
+        Ok(UserRecord {
+            username,
+            email,
+            age,
+            active,
+        })
+    }
+
+    fn value(&mut self) -> Result<Value, UserStoreError> {
+        match self.peek() {
+            Some('"') => Ok(Value::Str(self.string()?)),
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                while self.pos < self.chars.len() && self.chars[self.pos].is_ascii_digit() {
+                    self.pos += 1;
+                }
+                let digits: String = self.chars[start..self.pos].iter().collect();
+                digits
+                    .parse()
+                    .map(Value::Num)
+                    .map_err(|_| self.error("number out of range"))
+            }
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Value) -> Result<Value, UserStoreError> {
+        let end = self.pos + word.len();
+        if end <= self.chars.len() && self.chars[self.pos..end].iter().copied().eq(word.chars()) {
+            self.pos = end;
+            Ok(value)
+        } else {
+            Err(self.error(&format!("expected {}", word)))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, UserStoreError> {
+        self.expect('"')?;
+        let mut out = String::new();
+        loop {
+            let c = match self.chars.get(self.pos) {
+                Some(c) => *c,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(out),
+                '\\' => {
+                    let escaped = match self.chars.get(self.pos) {
+                        Some(c) => *c,
+                        None => return Err(self.error("unterminated escape")),
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        '"' => out.push('"'),
+                        '\\' => out.push('\\'),
+                        'n' => out.push('\n'),
+                        't' => out.push('\t'),
+                        'u' => {
+                            let end = self.pos + 4;
+                            if end > self.chars.len() {
+                                return Err(self.error("short unicode escape"));
+                            }
+                            let hex: String = self.chars[self.pos..end].iter().collect();
+                            let code = u32::from_str_radix(&hex, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error("bad unicode escape"))?;
+                            out.push(code);
+                            self.pos = end;
+                        }
+                        _ => return Err(self.error("unknown escape")),
+                    }
+                }
+                c => out.push(c),
+            }
+        }
+    }
+}
+
+pub fn json() {
+    it_round_trips_escaped_strings();
+    it_rejects_broken_escapes();
+}
+
+fn it_round_trips_escaped_strings() {
+    // every character quote escapes, and some it doesn't need to
+    let hostile = "quote\"tab\tslash\\ newline\n bell\u{7} nul\u{0} ünï 🦀";
+    let quoted = quote(hostile);
+    assert_eq!(
+        quoted,
+        "\"quote\\\"tab\\tslash\\\\ newline\\n bell\\u0007 nul\\u0000 ünï 🦀\""
+    );
+    assert_eq!(decode_string(&quoted).unwrap(), hostile);
+
+    // validation doesn't get a say here, json has to carry any record the backends are handed
+    let user = UserRecord::new(hostile, hostile, 7);
+    assert_eq!(decode_user(&encode_user(&user)).unwrap(), user);
+    let users = vec![
+        user.clone(),
+        UserRecord::new("plain", "plain@example.com", 1),
+    ];
+    assert_eq!(decode_users(&encode_users(&users)).unwrap(), users);
+}
+
+fn it_rejects_broken_escapes() {
+    assert!(decode_string("\"unterminated").is_err());
+    assert!(decode_string("\"bad \\x escape\"").is_err());
+    assert!(decode_string("\"short \\u12\"").is_err());
+    assert!(decode_string("\"a\" trailing").is_err());
+}
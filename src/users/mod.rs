@@ -0,0 +1,4 @@
+pub mod backend;
+pub mod builder;
+pub mod json;
+pub mod store;
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::users::backend::{JsonFileBackend, LogFileBackend, UserBackend};
+use crate::users::builder::{self, ValidationErrors};
+
+// A persistent user registry
+// The HashMap<String, i32> in main only keeps a name -> age pair in memory and is gone when the program exits.
+// UserStore keeps the full record for every user and hands every change to its backend straight away.
+// Where the users end up depends on the backend, see backend.rs. UserStore::open uses a JSON file.
+
+// Atomic write-rename
+// Writing straight into users.json and crashing half way through would leave a truncated file behind.
+// Instead we write the new contents into users.json.tmp, flush it to disk with sync_all and then rename it over
+// the old file. A rename inside the same directory is atomic, so the file on disk is always either the old
+// version or the new one, never something in between.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserRecord {
+    pub username: String,
+    pub email: String,
+    pub age: u32,
+    pub active: bool,
+}
+
+impl UserRecord {
+    pub fn new(username: &str, email: &str, age: u32) -> UserRecord {
+        UserRecord {
+            username: String::from(username),
+            email: String::from(email),
+            age,
+            active: true,
+        }
+    }
+}
+
+impl fmt::Display for UserRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.active { "active" } else { "inactive" };
+        write!(f, "{} <{}> age {} ({})", self.username, self.email, self.age, status)
+    }
+}
+
+// Fields that can be changed with `update`, None means keep the old value
+#[derive(Debug, Default, Clone)]
+pub struct UserUpdate {
+    pub email: Option<String>,
+    pub age: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum UserStoreError {
+    Io(io::Error),
+    Parse(String),
+    AlreadyExists(String),
+    NotFound(String),
+    Invalid(ValidationErrors),
+    Usage(String),
+}
+
+impl fmt::Display for UserStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UserStoreError::Io(err) => write!(f, "io error: {}", err),
+            UserStoreError::Parse(msg) => write!(f, "corrupt user file: {}", msg),
+            UserStoreError::AlreadyExists(name) => write!(f, "user {} already exists", name),
+            UserStoreError::NotFound(name) => write!(f, "user {} not found", name),
+            UserStoreError::Invalid(errors) => write!(f, "{}", errors),
+            UserStoreError::Usage(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for UserStoreError {}
+
+impl From<io::Error> for UserStoreError {
+    fn from(err: io::Error) -> UserStoreError {
+        UserStoreError::Io(err)
+    }
+}
+
+pub struct UserStore<B: UserBackend = JsonFileBackend> {
+    backend: B,
+    users: HashMap<String, UserRecord>,
+}
+
+impl UserStore<JsonFileBackend> {
+    // Opens the JSON registry at path, a missing file is treated as an empty registry
+    pub fn open(path: impl AsRef<Path>) -> Result<UserStore<JsonFileBackend>, UserStoreError> {
+        UserStore::with_backend(JsonFileBackend::new(path))
+    }
+}
+
+impl From<ValidationErrors> for UserStoreError {
+    fn from(errors: ValidationErrors) -> UserStoreError {
+        UserStoreError::Invalid(errors)
+    }
+}
+
+impl<B: UserBackend> UserStore<B> {
+    pub fn with_backend(backend: B) -> Result<UserStore<B>, UserStoreError> {
+        let users = backend.load()?;
+        Ok(UserStore {
+            backend,
+            users: users
+                .into_iter()
+                .map(|user| (user.username.clone(), user))
+                .collect(),
+        })
+    }
+
+    // Records built by hand with UserRecord::new are checked against the same rules as the builder
+    pub fn add(&mut self, user: UserRecord) -> Result<(), UserStoreError> {
+        let mut errors = builder::validate_username(&user.username);
+        errors.extend(builder::validate_email(&user.email));
+        if !errors.is_empty() {
+            return Err(ValidationErrors(errors).into());
+        }
+        if self.users.contains_key(&user.username) {
+            return Err(UserStoreError::AlreadyExists(user.username));
+        }
+        self.backend.put(&user)?;
+        self.users.insert(user.username.clone(), user);
+        Ok(())
+    }
+
+    pub fn get(&self, username: &str) -> Option<&UserRecord> {
+        self.users.get(username)
+    }
+
+    pub fn update(&mut self, username: &str, update: UserUpdate) -> Result<(), UserStoreError> {
+        let mut user = self.get_cloned(username)?;
+        if let Some(email) = update.email {
+            let errors = builder::validate_email(&email);
+            if !errors.is_empty() {
+                return Err(ValidationErrors(errors).into());
+            }
+            user.email = email;
+        }
+        if let Some(age) = update.age {
+            user.age = age;
+        }
+        self.replace(user)
+    }
+
+    // Deactivated users are kept in the registry, delete removes them for good
+    pub fn deactivate(&mut self, username: &str) -> Result<(), UserStoreError> {
+        let mut user = self.get_cloned(username)?;
+        user.active = false;
+        self.replace(user)
+    }
+
+    pub fn delete(&mut self, username: &str) -> Result<UserRecord, UserStoreError> {
+        if !self.users.contains_key(username) {
+            return Err(UserStoreError::NotFound(String::from(username)));
+        }
+        self.backend.remove(username)?;
+        Ok(self.users.remove(username).unwrap())
+    }
+
+    // Users sorted by username so the output is stable between runs
+    pub fn list(&self) -> Vec<&UserRecord> {
+        let mut users: Vec<&UserRecord> = self.users.values().collect();
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        users
+    }
+
+    fn get_cloned(&self, username: &str) -> Result<UserRecord, UserStoreError> {
+        self.users
+            .get(username)
+            .cloned()
+            .ok_or_else(|| UserStoreError::NotFound(String::from(username)))
+    }
+
+    // The backend is written first so memory never runs ahead of what was persisted
+    fn replace(&mut self, user: UserRecord) -> Result<(), UserStoreError> {
+        self.backend.put(&user)?;
+        self.users.insert(user.username.clone(), user);
+        Ok(())
+    }
+}
+
+// Command line interface
+// cargo run -- users add <username> <email> <age>
+// cargo run -- users update <username> [--email <email>] [--age <age>]
+// cargo run -- users deactivate <username>
+// cargo run -- users delete <username>
+// cargo run -- users list
+// The registry lives in users.json unless USER_STORE_PATH points somewhere else.
+// USER_STORE_BACKEND=log switches from the JSON file to the append-only log.
+
+const USAGE: &str = "usage: users <add|update|deactivate|delete|list> ...";
+
+pub fn run_cli(args: &[String]) -> Result<(), UserStoreError> {
+    let path = std::env::var("USER_STORE_PATH").unwrap_or_else(|_| String::from("users.json"));
+    let backend: Box<dyn UserBackend> = match std::env::var("USER_STORE_BACKEND").as_deref() {
+        Ok("log") => Box::new(LogFileBackend::new(path)),
+        Ok("json") | Err(_) => Box::new(JsonFileBackend::new(path)),
+        Ok(other) => {
+            return Err(UserStoreError::Usage(format!("unknown backend {}", other)));
+        }
+    };
+    let mut store = UserStore::with_backend(backend)?;
+
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    match args.as_slice() {
+        ["add", username, email, age] => {
+            let user = UserRecord::builder()
+                .username(username)
+                .email(email)
+                .age(parse_age(age)?)
+                .build()?;
+            store.add(user)?;
+            println!("added {}", username);
+        }
+        ["update", username, rest @ ..] => {
+            let update = parse_update(rest)?;
+            store.update(username, update)?;
+            println!("updated {}", username);
+        }
+        ["deactivate", username] => {
+            store.deactivate(username)?;
+            println!("deactivated {}", username);
+        }
+        ["delete", username] => {
+            let user = store.delete(username)?;
+            println!("deleted {}", user);
+        }
+        ["list"] => {
+            for user in store.list() {
+                println!("{}", user);
+            }
+        }
+        _ => return Err(UserStoreError::Usage(String::from(USAGE))),
+    }
+    Ok(())
+}
+
+fn parse_age(age: &str) -> Result<u32, UserStoreError> {
+    age.parse()
+        .map_err(|_| UserStoreError::Usage(format!("age must be a number, got {}", age)))
+}
+
+fn parse_update(args: &[&str]) -> Result<UserUpdate, UserStoreError> {
+    let mut update = UserUpdate::default();
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| UserStoreError::Usage(format!("missing value for {}", flag)))?;
+        match *flag {
+            "--email" => update.email = Some(String::from(*value)),
+            "--age" => update.age = Some(parse_age(value)?),
+            _ => return Err(UserStoreError::Usage(format!("unknown flag {}", flag))),
+        }
+    }
+    Ok(update)
+}
+
+pub fn user_store() {
+    let path = std::env::temp_dir().join(format!("rust-notes-users-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    it_persists_users_across_reopen(&path);
+    it_rejects_duplicate_and_missing_users(&path);
+    it_round_trips_special_characters(&path);
+    it_validates_users_before_storing(&path);
+
+    let _ = fs::remove_file(&path);
+}
+
+fn it_persists_users_across_reopen(path: &Path) {
+    let mut store = UserStore::open(path).unwrap();
+    store.add(UserRecord::new("Kartik", "kartik@example.com", 22)).unwrap();
+    store.add(UserRecord::new("Ashvin", "ashvin@example.com", 23)).unwrap();
+    store
+        .update(
+            "Kartik",
+            UserUpdate {
+                email: Some(String::from("kartik@work.example.com")),
+                age: None,
+            },
+        )
+        .unwrap();
+    store.deactivate("Ashvin").unwrap();
+
+    // nothing is held back in memory, a fresh store sees every change
+    let store = UserStore::open(path).unwrap();
+    let users = store.list();
+    assert_eq!(users.len(), 2);
+    assert_eq!(users[0].username, "Ashvin");
+    assert!(!users[0].active);
+    assert_eq!(users[1].email, "kartik@work.example.com");
+    assert_eq!(users[1].age, 22);
+
+    let mut tmp_name = path.as_os_str().to_owned();
+    tmp_name.push(".tmp");
+    assert!(!PathBuf::from(tmp_name).exists());
+}
+
+fn it_rejects_duplicate_and_missing_users(path: &Path) {
+    let mut store = UserStore::open(path).unwrap();
+    let duplicate = store.add(UserRecord::new("Kartik", "other@example.com", 30));
+    assert!(matches!(duplicate, Err(UserStoreError::AlreadyExists(_))));
+    assert!(matches!(
+        store.deactivate("Nobody"),
+        Err(UserStoreError::NotFound(_))
+    ));
+
+    let deleted = store.delete("Kartik").unwrap();
+    assert_eq!(deleted.email, "kartik@work.example.com");
+    assert!(UserStore::open(path).unwrap().get("Kartik").is_none());
+}
+
+fn it_round_trips_special_characters(path: &Path) {
+    let mut store = UserStore::open(path).unwrap();
+    // usernames are plain ASCII, the quoted local part of an email can hold quotes and backslashes
+    let email = "\"ünï\\\"quote\\\\slash\"@example.com";
+    store.add(UserRecord::new("Special", email, 1)).unwrap();
+
+    let store = UserStore::open(path).unwrap();
+    let user = store.get("Special").unwrap();
+    assert_eq!(user.email, email);
+    // the tabs and control characters validation keeps out are covered in json.rs
+}
+
+fn it_validates_users_before_storing(path: &Path) {
+    let mut store = UserStore::open(path).unwrap();
+    let invalid = store.add(UserRecord::new("x", "not-an-email", 1));
+    match invalid {
+        Err(UserStoreError::Invalid(errors)) => assert_eq!(errors.0.len(), 2),
+        other => panic!("expected validation errors, got {:?}", other),
+    }
+
+    let update = UserUpdate {
+        email: Some(String::from("broken@")),
+        age: None,
+    };
+    assert!(matches!(
+        store.update("Special", update),
+        Err(UserStoreError::Invalid(_))
+    ));
+    assert!(store.get("x").is_none());
+}
@@ -0,0 +1,221 @@
+// #[derive(Summary)]
+// A derive macro is a function the compiler calls with the tokens of the struct it is put on, and
+// whatever tokens it returns are added to the crate. syn parses the tokens into a syntax tree, quote!
+// turns Rust code with #variables spliced in back into tokens. Proc macros have to live in a crate of
+// their own (proc-macro = true in Cargo.toml), which is why this is a separate crate.
+//
+// The generated impl names the trait as plain `Summary`, so the trait has to be in scope where the
+// derive is used, the same as the book's HelloMacro.
+//
+//  #[derive(Summary)]
+//  #[summary(format = "{name} is {age} years old")]   optional, how summarize reads, fields by name
+//  struct User {
+//      #[summary(author)]                             exactly one, returned by summarize_author
+//      name: String,
+//      age: u32,
+//      #[summary(skip)]                               left out of summarize
+//      password: String,
+//  }
+//
+// Without a format summarize is `author (field: value, ...)` over the fields that aren't skipped.
+// Every field summarize shows has to implement Display.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr};
+
+#[proc_macro_derive(Summary, attributes(summary))]
+pub fn derive_summary(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+struct SummaryField<'a> {
+    ident: &'a Ident,
+    author: bool,
+    skip: bool,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "Summary can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Summary can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let format = container_format(input)?;
+
+    let mut fields = Vec::new();
+    for field in named {
+        let mut summary_field = SummaryField {
+            ident: field.ident.as_ref().unwrap(),
+            author: false,
+            skip: false,
+        };
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("summary")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("author") {
+                    summary_field.author = true;
+                    Ok(())
+                } else if meta.path.is_ident("skip") {
+                    summary_field.skip = true;
+                    Ok(())
+                } else if meta.path.is_ident("format") {
+                    Err(meta.error("format goes on the struct, not on a field"))
+                } else {
+                    Err(meta.error("unknown summary attribute, expected author or skip"))
+                }
+            })?;
+        }
+        if summary_field.author && summary_field.skip {
+            return Err(syn::Error::new_spanned(
+                field,
+                "the author field can't be skipped",
+            ));
+        }
+        fields.push(summary_field);
+    }
+
+    let mut authors = fields.iter().filter(|field| field.author);
+    let author = match (authors.next(), authors.next()) {
+        (Some(author), None) => author.ident,
+        (None, _) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "one field needs #[summary(author)]",
+            ))
+        }
+        (Some(_), Some(second)) => {
+            return Err(syn::Error::new_spanned(
+                second.ident,
+                "only one field can be #[summary(author)]",
+            ))
+        }
+    };
+
+    let summarize = match format {
+        Some(format) => {
+            let names = placeholders(&format)?;
+            for name in &names {
+                match fields.iter().find(|field| field.ident == name) {
+                    None => {
+                        return Err(syn::Error::new_spanned(
+                            &format,
+                            format!("no field named `{}`", name),
+                        ))
+                    }
+                    Some(field) if field.skip => {
+                        return Err(syn::Error::new_spanned(
+                            &format,
+                            format!("field `{}` is skipped", name),
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+            let names = names.iter().map(|name| Ident::new(name, format.span()));
+            quote! { ::std::format!(#format, #(#names = self.#names),*) }
+        }
+        None => {
+            let shown: Vec<&Ident> = fields
+                .iter()
+                .filter(|field| !field.skip && !field.author)
+                .map(|field| field.ident)
+                .collect();
+            if shown.is_empty() {
+                quote! { ::std::string::ToString::to_string(&self.#author) }
+            } else {
+                let format = shown
+                    .iter()
+                    .map(|ident| format!("{}: {{}}", ident))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let format = format!("{{}} ({})", format);
+                // spanned so a field that isn't Display is the one the error points at
+                let shown = shown
+                    .iter()
+                    .map(|ident| quote_spanned! { ident.span()=> self.#ident });
+                quote! { ::std::format!(#format, self.#author, #(#shown),*) }
+            }
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics Summary for #name #ty_generics #where_clause {
+            fn summarize_author(&self) -> ::std::string::String {
+                ::std::string::ToString::to_string(&self.#author)
+            }
+
+            fn summarize(&self) -> ::std::string::String {
+                #summarize
+            }
+        }
+    })
+}
+
+// #[summary(format = "...")] on the struct
+fn container_format(input: &DeriveInput) -> syn::Result<Option<LitStr>> {
+    let mut format = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("summary")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("format") {
+                format = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else if meta.path.is_ident("author") || meta.path.is_ident("skip") {
+                Err(meta.error("author and skip go on fields, not on the struct"))
+            } else {
+                Err(meta.error("unknown summary attribute, expected format"))
+            }
+        })?;
+    }
+    Ok(format)
+}
+
+// The field names in a format string, `{name}` or `{name:>10}`. {{ and }} are literal braces.
+// Positional `{}` has nothing to refer to and is an error.
+fn placeholders(format: &LitStr) -> syn::Result<Vec<String>> {
+    let text = format.value();
+    let mut names: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '{' {
+            continue;
+        }
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            continue;
+        }
+        let name: String = chars
+            .by_ref()
+            .take_while(|c| *c != '}' && *c != ':')
+            .collect();
+        if name.is_empty() {
+            return Err(syn::Error::new_spanned(
+                format,
+                "name the field in every placeholder, like {name}",
+            ));
+        }
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
@@ -0,0 +1,9 @@
+// Compiles every file in ui/pass (and runs its main) and checks every file in ui/fail fails to
+// compile with the error in the .stderr file next to it. After changing an error message,
+// TRYBUILD=overwrite cargo test -p summary_derive writes the new .stderr files.
+#[test]
+fn ui() {
+    let cases = trybuild::TestCases::new();
+    cases.pass("tests/ui/pass/*.rs");
+    cases.compile_fail("tests/ui/fail/*.rs");
+}
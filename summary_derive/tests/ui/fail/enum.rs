@@ -0,0 +1,16 @@
+use summary_derive::Summary;
+
+pub trait Summary {
+    fn summarize_author(&self) -> String;
+
+    fn summarize(&self) -> String {
+        format!("(Read more from {}...)", self.summarize_author())
+    }
+}
+
+#[derive(Summary)]
+enum Shape {
+    Circle,
+}
+
+fn main() {}
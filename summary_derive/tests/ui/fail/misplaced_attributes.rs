@@ -0,0 +1,23 @@
+use summary_derive::Summary;
+
+pub trait Summary {
+    fn summarize_author(&self) -> String;
+
+    fn summarize(&self) -> String {
+        format!("(Read more from {}...)", self.summarize_author())
+    }
+}
+
+#[derive(Summary)]
+#[summary(author)]
+struct User {
+    name: String,
+}
+
+#[derive(Summary)]
+struct Post {
+    #[summary(author, format = "{author}")]
+    author: String,
+}
+
+fn main() {}
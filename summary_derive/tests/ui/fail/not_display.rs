@@ -0,0 +1,18 @@
+use summary_derive::Summary;
+
+pub trait Summary {
+    fn summarize_author(&self) -> String;
+
+    fn summarize(&self) -> String {
+        format!("(Read more from {}...)", self.summarize_author())
+    }
+}
+
+#[derive(Summary)]
+struct User {
+    #[summary(author)]
+    name: String,
+    tags: Vec<String>,
+}
+
+fn main() {}
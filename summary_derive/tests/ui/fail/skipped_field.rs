@@ -0,0 +1,20 @@
+use summary_derive::Summary;
+
+pub trait Summary {
+    fn summarize_author(&self) -> String;
+
+    fn summarize(&self) -> String {
+        format!("(Read more from {}...)", self.summarize_author())
+    }
+}
+
+#[derive(Summary)]
+#[summary(format = "{name}: {password}")]
+struct User {
+    #[summary(author)]
+    name: String,
+    #[summary(skip)]
+    password: String,
+}
+
+fn main() {}
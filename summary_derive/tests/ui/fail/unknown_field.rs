@@ -0,0 +1,19 @@
+use summary_derive::Summary;
+
+pub trait Summary {
+    fn summarize_author(&self) -> String;
+
+    fn summarize(&self) -> String {
+        format!("(Read more from {}...)", self.summarize_author())
+    }
+}
+
+#[derive(Summary)]
+#[summary(format = "{name} is {years} years old")]
+struct User {
+    #[summary(author)]
+    name: String,
+    age: u32,
+}
+
+fn main() {}
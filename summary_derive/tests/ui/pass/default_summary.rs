@@ -0,0 +1,58 @@
+use summary_derive::Summary;
+
+pub trait Summary {
+    fn summarize_author(&self) -> String;
+
+    fn summarize(&self) -> String {
+        format!("(Read more from {}...)", self.summarize_author())
+    }
+}
+
+#[derive(Summary)]
+struct Post {
+    title: String,
+    #[summary(author)]
+    username: String,
+    likes: u32,
+    #[summary(skip)]
+    draft: Vec<u8>,
+}
+
+#[derive(Summary)]
+struct Tag {
+    #[summary(author)]
+    name: &'static str,
+}
+
+// generics and where clauses are carried over to the impl
+#[derive(Summary)]
+struct Wrapper<T>
+where
+    T: std::fmt::Display,
+{
+    #[summary(author)]
+    owner: T,
+    #[summary(skip)]
+    _inner: Option<T>,
+}
+
+fn main() {
+    let post = Post {
+        title: String::from("Traits"),
+        username: String::from("kartik"),
+        likes: 3,
+        draft: vec![],
+    };
+    assert_eq!(post.summarize(), "kartik (title: Traits, likes: 3)");
+    assert!(post.draft.is_empty());
+
+    let tag = Tag { name: "rust" };
+    assert_eq!(tag.summarize(), "rust");
+
+    let wrapper = Wrapper {
+        owner: 7,
+        _inner: None,
+    };
+    assert_eq!(wrapper.summarize_author(), "7");
+    assert_eq!(wrapper.summarize(), "7");
+}
@@ -0,0 +1,29 @@
+use summary_derive::Summary;
+
+pub trait Summary {
+    fn summarize_author(&self) -> String;
+
+    fn summarize(&self) -> String {
+        format!("(Read more from {}...)", self.summarize_author())
+    }
+}
+
+#[derive(Summary)]
+#[summary(format = "{name} is {age} years old, {{braces}} and {name:>8}")]
+struct User {
+    #[summary(author)]
+    name: String,
+    age: u32,
+}
+
+fn main() {
+    let user = User {
+        name: String::from("Kartik"),
+        age: 22,
+    };
+    assert_eq!(user.summarize_author(), "Kartik");
+    assert_eq!(
+        user.summarize(),
+        "Kartik is 22 years old, {braces} and   Kartik"
+    );
+}
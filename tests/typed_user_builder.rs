@@ -0,0 +1,9 @@
+// TypedUserBuilder only has build() once both username and email are set, every file in
+// ui/typed_user_builder calls it too early and has to fail with the error in its .stderr file.
+// rust-notes has no library target, so the files pull in src/users with #[path].
+// TRYBUILD=overwrite cargo test --test typed_user_builder writes new .stderr files.
+#[test]
+fn typed_user_builder() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/typed_user_builder/*.rs");
+}
@@ -0,0 +1,8 @@
+#[path = "../../../src/users/mod.rs"]
+mod users;
+
+use users::builder::TypedUserBuilder;
+
+fn main() {
+    let _ = TypedUserBuilder::new().email("kartik@example.com").build();
+}